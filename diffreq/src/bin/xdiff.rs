@@ -1,32 +1,89 @@
 use clap::Parser;
+use console::style;
 use dialoguer::{theme, Input, MultiSelect};
 use diffreq::{
-    util::hightlight_text, Action, Args, ConfigLoad, DiffConfig, DiffProfile, ExtraArgs,
-    GetProfile, RequestProfile, ResponseProfile, RunArgs,
+    report::{render, DiffReport},
+    util::hightlight_text,
+    Action, Args, ClientConfig, ConfigLoad, DiffConfig, DiffProfile, ExtraArgs, Har, ImportArgs,
+    MirrorArgs, RequestProfile, ResponseProfile, RunArgs,
 };
+use futures::future::join_all;
 use std::io::{self, Write};
 
 use anyhow::Result;
 
+// 指定 --exit-code 时的退出码，和 `git diff --exit-code` 类似
+const EXIT_DIFFERENT: i32 = 1;
+const EXIT_ERROR: i32 = 2;
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli_args = Args::parse();
     match cli_args.action {
-        Action::Run(run_args) => run(run_args).await?,
+        Action::Run(run_args) => {
+            let exit_code = run_args.exit_code;
+            match run(run_args).await {
+                Ok(same) if exit_code && !same => std::process::exit(EXIT_DIFFERENT),
+                Err(e) if exit_code => {
+                    eprintln!("Error: {:?}", e);
+                    std::process::exit(EXIT_ERROR);
+                }
+                res => res?,
+            };
+        }
         Action::Parse => parse_profile().await?,
+        Action::Import(import_args) => import(import_args)?,
+        Action::Mirror(mirror_args) => mirror(mirror_args).await?,
         _ => Err(anyhow::anyhow!("unknown action"))?,
     };
     Ok(())
 }
 
+// 作为代理运行，把请求同时转发给 primary 和 candidate，输出每个请求的 diff
+async fn mirror(args: MirrorArgs) -> Result<()> {
+    let config = args.config.unwrap_or_else(|| "./xdiff.yml".to_string());
+    let config_profile = DiffConfig::load_yaml(&config).await?;
+    let mut mirror = config_profile
+        .mirror
+        .ok_or_else(|| anyhow::anyhow!("mirror not found in config: {}", config))?;
+    if let Some(mode) = args.mode {
+        mirror.res.mode = mode;
+    }
+    let (primary, candidate) = (mirror.primary.clone(), mirror.candidate.clone());
+    let (addr, server) = mirror.bind(&config_profile.client, args.addr)?;
+    println!(
+        "{} http://{} -> {} (primary), {} (candidate)",
+        style("Mirroring").green().bold(),
+        addr,
+        primary,
+        candidate
+    );
+    server.await
+}
+
+// 把 HAR 中的每个请求导入为一个 diff profile，req2 发送到 --base 指定的地址
+fn import(args: ImportArgs) -> Result<()> {
+    let config = Har::load(&args.file)?.to_diff_config(&args.base)?;
+    let result = serde_yaml::to_string(&config)?;
+    match args.output {
+        Some(path) => std::fs::write(path, result)?,
+        None => write!(
+            io::stdout().lock(),
+            "---\n{}",
+            hightlight_text(&result, "yaml", "base16-ocean.dark")?
+        )?,
+    }
+    Ok(())
+}
+
 async fn parse_profile() -> Result<()> {
     //  交互式地生成profile
     let theme = theme::ColorfulTheme::default();
     let url1: String = Input::with_theme(&theme)
-        .with_prompt("Url1")
+        .with_prompt("Url1 or curl command")
         .interact_text()?;
     let url2: String = Input::with_theme(&theme)
-        .with_prompt("Url2")
+        .with_prompt("Url2 or curl command")
         .interact_text()?;
 
     // RequestProfile from String
@@ -38,7 +95,9 @@ async fn parse_profile() -> Result<()> {
         .with_prompt("Profile")
         .interact_text()?;
 
-    let response1 = req1.send(&ExtraArgs::default()).await?;
+    let response1 = req1
+        .send(&ClientConfig::default().build()?, &ExtraArgs::default())
+        .await?;
     let headers_key = response1.get_header_keys();
     let chosen = MultiSelect::with_theme(&theme)
         .with_prompt("Select headers to skip")
@@ -66,19 +125,79 @@ async fn parse_profile() -> Result<()> {
     Ok(())
 }
 
-async fn run(args: RunArgs) -> Result<()> {
+// 返回所有 profile 的响应是否一致
+async fn run(args: RunArgs) -> Result<bool> {
     //      如果没有值得则使用默认的xdiff.yml
     let config = args.config.unwrap_or_else(|| "./xdiff.yml".to_string());
-    let profile_name = args.profile;
-    let config_profile = DiffConfig::load_yaml(&config).await?;
-    let profile = config_profile.get_profile(&profile_name).ok_or_else(|| {
-        anyhow::anyhow!("Profile: {} not found in config: {}", profile_name, config)
-    })?;
-    let extra_args = args.extra_params.into();
-    let diff_text = profile.diff(extra_args).await?;
-    let mut stdout = io::stdout().lock();
-    stdout.write_all(diff_text.as_bytes())?;
-    // print to stdout
+    let mut config_profile = DiffConfig::load_yaml(&config).await?;
+    // 命令行指定的 mode 优先级高于配置文件
+    if let Some(mode) = args.mode {
+        for profile in config_profile.profiles.values_mut() {
+            profile.res.mode = mode;
+        }
+    }
+    let pattern = if args.all {
+        None
+    } else {
+        args.profile.as_deref()
+    };
+    let profiles = config_profile.select_profiles(pattern, &args.tag);
+    if profiles.is_empty() {
+        return Err(anyhow::anyhow!(
+            "Profile: {} not found in config: {}",
+            pattern.unwrap_or("*"),
+            config
+        ));
+    }
 
-    Ok(())
+    // 所有的 profile 并发执行，共用同一个 client
+    let client = config_profile.client.build()?;
+    let mut extra_args: ExtraArgs = args.extra_params.into();
+    if let Some(oauth2) = &config_profile.oauth2 {
+        extra_args = extra_args.with_token(oauth2.token(&client).await?);
+    }
+    let results = join_all(
+        profiles
+            .iter()
+            .map(|(_, profile)| profile.diff(&client, extra_args.clone(), args.update)),
+    )
+    .await;
+    let reports: Vec<_> = profiles
+        .into_iter()
+        .zip(results)
+        .map(|((name, profile), result)| DiffReport::new(name, profile, result))
+        .collect();
+
+    let output = render(&reports, args.output.unwrap_or_default())?;
+    match args.output_file {
+        Some(path) => tokio::fs::write(path, output).await?,
+        None => {
+            // print to stdout
+            let mut stdout = io::stdout().lock();
+            stdout.write_all(output.as_bytes())?;
+            stdout.flush()?;
+        }
+    }
+
+    if args.update {
+        for report in reports.iter().filter(|r| r.error.is_none()) {
+            if let Some(path) = report.snapshot {
+                eprintln!("Snapshot updated: {}", path);
+            }
+        }
+    }
+
+    // 只有一个 profile 时直接返回原始的错误
+    if let [DiffReport { error: Some(e), .. }] = &reports[..] {
+        return Err(anyhow::anyhow!("{}", e));
+    }
+    let errors = reports.iter().filter(|r| r.error.is_some()).count();
+    if errors > 0 {
+        return Err(anyhow::anyhow!(
+            "{} of {} profiles failed",
+            errors,
+            reports.len()
+        ));
+    }
+    Ok(reports.iter().all(|r| r.same))
 }
@@ -1,26 +1,45 @@
 use clap::Parser;
+use console::style;
 use dialoguer::{theme, Input};
 use diffreq::{
-    get_body_text, get_header_text, get_status_text, util::hightlight_text, Action, Args,
-    ConfigLoad, GetProfile, RequestConfig, RequestProfile, RunArgs,
+    get_header_text, get_status_text,
+    util::{hightlight_text, relative_path},
+    ConfigLoad, ExportArgs, ExtraArgs, FlowArgs, GetProfile, Har, HarEntry, ImportFormat,
+    MockConfig, OpenApi, ReqAction, ReqArgs, ReqImportArgs, ReqRunArgs, RequestConfig,
+    RequestProfile, ResponseProfile, ServeArgs, TestArgs,
+};
+use reqwest::Client;
+use std::{
+    io::{self, Write},
+    path::{Path, PathBuf},
+    time::{Instant, SystemTime},
 };
-use std::io::{self, Write};
 use string_builder::Builder;
 
 use anyhow::Result;
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let cli_args = Args::parse();
+    let cli_args = ReqArgs::parse();
     match cli_args.action {
-        Action::Run(run_args) => run(run_args).await?,
-        Action::Parse => parse_profile().await?,
+        ReqAction::Run(run_args) => run(run_args).await?,
+        ReqAction::Parse => parse_profile().await?,
+        ReqAction::Import(import_args) => import(import_args)?,
+        ReqAction::Export(export_args) => export(export_args).await?,
+        ReqAction::Flow(flow_args) => flow(flow_args).await?,
+        ReqAction::Serve(serve_args) => serve(serve_args).await?,
+        ReqAction::Test(test_args) => {
+            // 有断言失败时返回非 0 的退出码
+            if !test(test_args).await? {
+                std::process::exit(1);
+            }
+        }
         _ => Err(anyhow::anyhow!("unknown action"))?,
     };
     Ok(())
 }
 
-async fn run(args: RunArgs) -> Result<()> {
+async fn run(args: ReqRunArgs) -> Result<()> {
     //      如果没有值得则使用默认的xdiff.yml
     let config = args.config.unwrap_or_else(|| "./xreq.yml".to_string());
     let profile_name = args.profile;
@@ -28,12 +47,28 @@ async fn run(args: RunArgs) -> Result<()> {
     let req = config_profile.get_profile(&profile_name).ok_or_else(|| {
         anyhow::anyhow!("Profile: {} not found in config: {}", profile_name, config)
     })?;
-    let extra_args = args.extra_params.into();
-    let res = req.send(&extra_args).await?.into_inner();
+    let mut extra_args: ExtraArgs = args.extra_params.into();
+    let client = config_profile.client.build()?;
+    if let Some(oauth2) = &config_profile.oauth2 {
+        extra_args = extra_args.with_token(oauth2.token(&client).await?);
+    }
+    let request = req.build_request(&client, &extra_args)?;
+    let recorded = request.try_clone();
+    let (started, start) = (SystemTime::now(), Instant::now());
+    let res = req.execute(&client, request).await?;
 
-    let status_text = get_status_text(&res)?;
-    let header_text = get_header_text(&res, &[])?;
-    let body = get_body_text(res, &[]).await?;
+    let status_text = get_status_text(res.inner())?;
+    let header_text = get_header_text(res.inner(), &[])?;
+    let body_syntax = res.body_syntax();
+    let (raw, filtered) = res.filter_raw(&ResponseProfile::default()).await?;
+    if let (Some(path), Some(request)) = (&args.har, &recorded) {
+        let entry = HarEntry::new(request, &raw, started, start.elapsed());
+        Har::new(vec![entry]).save(path)?;
+    }
+    let body = match filtered.body {
+        serde_json::Value::String(text) => text,
+        body => serde_json::to_string_pretty(&body)?,
+    };
 
     // get res header and body text
     let mut output_builder = Builder::default();
@@ -45,7 +80,7 @@ async fn run(args: RunArgs) -> Result<()> {
     ));
     output_builder.append(format!(
         "{}\n",
-        hightlight_text(&body, "json", "base16-ocean.dark")?
+        hightlight_text(&body, body_syntax, "base16-ocean.dark")?
     ));
 
     let mut stdout = io::stdout().lock();
@@ -55,11 +90,217 @@ async fn run(args: RunArgs) -> Result<()> {
     Ok(())
 }
 
+// 依次发送选中的 profile 的请求，检查 assert，返回是否全部通过
+async fn test(args: TestArgs) -> Result<bool> {
+    let config = args.config.unwrap_or_else(|| "./xreq.yml".to_string());
+    let config_profile = RequestConfig::load_yaml(&config).await?;
+    let pattern = if args.all {
+        None
+    } else {
+        args.profile.as_deref()
+    };
+    let profiles = config_profile.select_profiles(pattern);
+    if profiles.is_empty() {
+        return Err(anyhow::anyhow!("No profile matched in config: {}", config));
+    }
+    let mut extra_args: ExtraArgs = args.extra_params.into();
+    let client = config_profile.client.build()?;
+    if let Some(oauth2) = &config_profile.oauth2 {
+        extra_args = extra_args.with_token(oauth2.token(&client).await?);
+    }
+
+    let mut output_builder = Builder::default();
+    let mut failed = 0;
+    let mut entries = vec![];
+    for (name, profile) in &profiles {
+        let start = Instant::now();
+        let result = check_profile(profile, &client, &extra_args, start, &mut entries).await;
+        let failures = match result {
+            Ok(failures) => failures,
+            Err(e) => vec![format!("error: {:#}", e)],
+        };
+        let elapsed = start.elapsed().as_millis();
+        if failures.is_empty() {
+            output_builder.append(format!(
+                "{} {} ({}ms)\n",
+                style("PASS").green(),
+                name,
+                elapsed
+            ));
+        } else {
+            failed += 1;
+            output_builder.append(format!(
+                "{} {} ({}ms)\n",
+                style("FAIL").red(),
+                name,
+                elapsed
+            ));
+            for failure in failures {
+                output_builder.append(format!("  - {}\n", failure));
+            }
+        }
+    }
+    output_builder.append(format!(
+        "\n{} passed, {} failed\n",
+        profiles.len() - failed,
+        failed
+    ));
+
+    if let Some(path) = &args.har {
+        Har::new(entries).save(path)?;
+    }
+
+    let mut stdout = io::stdout().lock();
+    stdout.write_all(output_builder.string()?.as_bytes())?;
+    Ok(failed == 0)
+}
+
+async fn check_profile(
+    profile: &RequestProfile,
+    client: &Client,
+    args: &ExtraArgs,
+    start: Instant,
+    entries: &mut Vec<HarEntry>,
+) -> Result<Vec<String>> {
+    let request = profile.build_request(client, args)?;
+    let recorded = request.try_clone();
+    let started = SystemTime::now();
+    let res = profile.execute(client, request).await?;
+    let status = res.inner().status().as_u16();
+    let (raw, res) = res.filter_raw(&ResponseProfile::default()).await?;
+    if let Some(request) = &recorded {
+        entries.push(HarEntry::new(request, &raw, started, start.elapsed()));
+    }
+    // 没有配置 assert 时，请求成功即通过
+    Ok(match &profile.assert {
+        Some(assert) => assert.check(status, &res, start.elapsed()),
+        None => vec![],
+    })
+}
+
+// 按顺序执行 flow 的每一步，输出每一步的状态和捕获的变量，以及最后一步的响应
+async fn flow(args: FlowArgs) -> Result<()> {
+    let config = args.config.unwrap_or_else(|| "./xreq.yml".to_string());
+    let flow_name = args.flow;
+    let config_profile = RequestConfig::load_yaml(&config).await?;
+    let flow = config_profile
+        .flows
+        .get(&flow_name)
+        .ok_or_else(|| anyhow::anyhow!("Flow: {} not found in config: {}", flow_name, config))?;
+    let mut extra_args: ExtraArgs = args.extra_params.into();
+    let client = config_profile.client.build()?;
+    if let Some(oauth2) = &config_profile.oauth2 {
+        extra_args = extra_args.with_token(oauth2.token(&client).await?);
+    }
+    let results = flow
+        .run(&config_profile.profiles, &client, &extra_args)
+        .await?;
+
+    let mut output_builder = Builder::default();
+    for (i, result) in results.iter().enumerate() {
+        output_builder.append(format!(
+            "{} {} {} ({}ms)\n",
+            style(format!("step {}:", i + 1)).bold(),
+            result.profile,
+            result.response.status,
+            result.elapsed.as_millis()
+        ));
+        for (name, value) in &result.captured {
+            output_builder.append(format!("  {} = {}\n", style(name).cyan(), value));
+        }
+    }
+    if let Some(last) = results.last() {
+        let res = &last.response;
+        let header_text: String = res
+            .headers
+            .iter()
+            .map(|(k, v)| format!("{}: {}\r\n", k, v))
+            .collect();
+        let (body, body_syntax) = match &res.body {
+            serde_json::Value::String(text) => (text.clone(), "txt"),
+            body => (serde_json::to_string_pretty(body)?, "json"),
+        };
+        output_builder.append(format!(
+            "\n{}{}\n{}\n",
+            hightlight_text(&format!("{}\r\n", res.status), "yaml", "Solarized (light)")?,
+            hightlight_text(&header_text, "yaml", "Solarized (dark)")?,
+            hightlight_text(&body, body_syntax, "base16-ocean.dark")?
+        ));
+    }
+
+    let mut stdout = io::stdout().lock();
+    stdout.write_all(output_builder.string()?.as_bytes())?;
+    Ok(())
+}
+
+// 启动 mock server，按配置中的 stub 返回响应，直到进程退出
+async fn serve(args: ServeArgs) -> Result<()> {
+    let config = args.config.unwrap_or_else(|| "./mock.yml".to_string());
+    let mock = MockConfig::load_yaml(&config).await?;
+    let stubs = mock.stubs.len();
+    let (addr, server) = mock.bind(args.addr)?;
+    println!(
+        "{} {} stubs from {} on http://{}",
+        style("Serving").green().bold(),
+        stubs,
+        config,
+        addr
+    );
+    server.await
+}
+
+async fn export(args: ExportArgs) -> Result<()> {
+    let config = args.config.unwrap_or_else(|| "./xreq.yml".to_string());
+    let config_profile = RequestConfig::load_yaml(&config).await?;
+    let req = config_profile.get_profile(&args.profile).ok_or_else(|| {
+        anyhow::anyhow!("Profile: {} not found in config: {}", args.profile, config)
+    })?;
+    let output = req.export(&args.extra_params.into(), args.format)?;
+
+    let mut stdout = io::stdout().lock();
+    writeln!(stdout, "{}", output)?;
+    Ok(())
+}
+
+// 把 HAR 中的每个请求（或者 OpenAPI 文档中的每个 operation）导入为一个 profile，输出 yaml 配置
+fn import(args: ReqImportArgs) -> Result<()> {
+    let config = match args.format {
+        ImportFormat::Har => Har::load(&args.file)?.to_request_config()?,
+        ImportFormat::Openapi => {
+            // 断言中的文档路径写成相对于输出文件所在目录的路径，和加载配置时的解析方式一致
+            let file = match &args.output {
+                Some(output) => {
+                    let dir = Path::new(output)
+                        .parent()
+                        .filter(|dir| !dir.as_os_str().is_empty())
+                        .unwrap_or_else(|| Path::new("."));
+                    relative_path(
+                        &std::fs::canonicalize(&args.file)?,
+                        &std::fs::canonicalize(dir)?,
+                    )
+                }
+                None => PathBuf::from(&args.file),
+            };
+            OpenApi::load(&args.file)?.to_request_config(&file, args.base.as_ref())?
+        }
+    };
+    let result = serde_yaml::to_string(&config)?;
+    match args.output {
+        Some(path) => std::fs::write(path, result)?,
+        None => write!(
+            io::stdout().lock(),
+            "---\n{}",
+            hightlight_text(&result, "yaml", "base16-ocean.dark")?
+        )?,
+    }
+    Ok(())
+}
+
 async fn parse_profile() -> Result<()> {
     //  交互式地生成profile
     let theme = theme::ColorfulTheme::default();
     let url: String = Input::with_theme(&theme)
-        .with_prompt("Url")
+        .with_prompt("Url or curl command")
         .interact_text()?;
 
     // RequestProfile from String
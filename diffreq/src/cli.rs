@@ -60,6 +60,7 @@ impl From<Vec<KeyVal>> for ExtraArgs {
             headers,
             query,
             body,
+            token: None,
         }
     }
 }
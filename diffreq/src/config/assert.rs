@@ -0,0 +1,274 @@
+use std::{collections::BTreeMap, fmt, time::Duration};
+
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{openapi::OpenApiAssert, ConfigValidate, FilteredResponse};
+use crate::jsonpath::JsonPath;
+
+// xreq test 使用的响应断言
+// ```yaml
+// assert:
+//   status: 200
+//   max_latency_ms: 500
+//   headers:
+//     content-type:
+//       regex: ^application/json
+//   body:
+//     - path: $.id
+//       eq: 1
+//     - path: $.tags
+//       type: array
+//   openapi:
+//     file: ./openapi.yml
+//     operation: getTodo
+// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AssertConfig {
+    /// expected status code
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub status: Option<u16>,
+    /// checks of the response headers, the header names are lowercase
+    #[serde(skip_serializing_if = "BTreeMap::is_empty", default)]
+    pub headers: BTreeMap<String, ValueMatch>,
+    /// checks of the values selected by the json paths
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub body: Vec<BodyAssert>,
+    /// max latency of the request, including reading the body
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub max_latency_ms: Option<u64>,
+    /// validate the body by the response schema of an openapi operation
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub openapi: Option<OpenApiAssert>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BodyAssert {
+    pub path: String,
+    #[serde(flatten)]
+    pub matcher: ValueMatch,
+}
+
+// 所有指定的条件都需要满足，json path 选中多个值时，每一个值都需要满足
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ValueMatch {
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub eq: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub regex: Option<String>,
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none", default)]
+    pub kind: Option<JsonType>,
+    /// `false` means the value must not exist
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub exists: Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JsonType {
+    String,
+    Number,
+    Integer,
+    Boolean,
+    Array,
+    Object,
+    Null,
+}
+
+impl JsonType {
+    pub(super) fn of(value: &Value) -> Self {
+        match value {
+            Value::String(_) => JsonType::String,
+            Value::Number(n) if n.is_i64() || n.is_u64() => JsonType::Integer,
+            Value::Number(_) => JsonType::Number,
+            Value::Bool(_) => JsonType::Boolean,
+            Value::Array(_) => JsonType::Array,
+            Value::Object(_) => JsonType::Object,
+            Value::Null => JsonType::Null,
+        }
+    }
+
+    pub(super) fn matches(&self, value: &Value) -> bool {
+        match (self, JsonType::of(value)) {
+            (JsonType::Number, JsonType::Integer) => true,
+            (expected, actual) => *expected == actual,
+        }
+    }
+}
+
+impl fmt::Display for JsonType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            JsonType::String => "string",
+            JsonType::Number => "number",
+            JsonType::Integer => "integer",
+            JsonType::Boolean => "boolean",
+            JsonType::Array => "array",
+            JsonType::Object => "object",
+            JsonType::Null => "null",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl AssertConfig {
+    /// check the response, return the failure messages
+    pub fn check(&self, status: u16, res: &FilteredResponse, elapsed: Duration) -> Vec<String> {
+        let mut failures = vec![];
+        if let Some(expected) = self.status {
+            if expected != status {
+                failures.push(format!("status: expected {}, got {}", expected, status));
+            }
+        }
+        if let Some(max) = self.max_latency_ms {
+            let elapsed = elapsed.as_millis();
+            if elapsed > max as u128 {
+                failures.push(format!("latency: {}ms exceeds {}ms", elapsed, max));
+            }
+        }
+        for (name, matcher) in &self.headers {
+            let value = res
+                .headers
+                .get(&name.to_lowercase())
+                .map(|v| Value::String(v.clone()));
+            let label = format!("header {}", name);
+            failures.extend(matcher.check(&label, value.as_ref()));
+        }
+        for assert in &self.body {
+            // 配置加载时已经检查过 json path
+            let values = match assert.path.parse::<JsonPath>() {
+                Ok(path) => path.select(&res.body),
+                Err(e) => {
+                    failures.push(format!("{}: {}", assert.path, e));
+                    continue;
+                }
+            };
+            if values.is_empty() {
+                failures.extend(assert.matcher.check(&assert.path, None));
+            }
+            for value in values {
+                failures.extend(assert.matcher.check(&assert.path, Some(value)));
+            }
+        }
+        if let Some(openapi) = &self.openapi {
+            failures.extend(openapi.check(status, &res.body));
+        }
+        failures
+    }
+}
+
+impl ValueMatch {
+    fn check(&self, label: &str, value: Option<&Value>) -> Vec<String> {
+        let value = match (self.exists, value) {
+            (Some(false), Some(v)) => {
+                return vec![format!("{}: expected not to exist, got {}", label, v)]
+            }
+            (Some(false), None) => return vec![],
+            (_, None) => return vec![format!("{}: not found", label)],
+            (_, Some(v)) => v,
+        };
+        let mut failures = vec![];
+        if let Some(eq) = &self.eq {
+            if eq != value {
+                failures.push(format!("{}: expected {}, got {}", label, eq, value));
+            }
+        }
+        if let Some(regex) = &self.regex {
+            let text = value
+                .as_str()
+                .map_or_else(|| value.to_string(), |s| s.to_string());
+            match Regex::new(regex) {
+                Ok(re) if re.is_match(&text) => {}
+                Ok(_) => failures.push(format!(
+                    "{}: {} does not match regex {}",
+                    label, value, regex
+                )),
+                Err(e) => failures.push(format!("{}: invalid regex {}: {}", label, regex, e)),
+            }
+        }
+        if let Some(kind) = self.kind {
+            if !kind.matches(value) {
+                failures.push(format!(
+                    "{}: expected type {}, got {}",
+                    label,
+                    kind,
+                    JsonType::of(value)
+                ));
+            }
+        }
+        failures
+    }
+}
+
+impl ConfigValidate for AssertConfig {
+    fn validate(&self) -> Result<()> {
+        let regexes = self
+            .headers
+            .values()
+            .chain(self.body.iter().map(|b| &b.matcher))
+            .filter_map(|m| m.regex.as_ref());
+        for regex in regexes {
+            Regex::new(regex).with_context(|| format!("assert regex: {}", regex))?;
+        }
+        for assert in &self.body {
+            assert
+                .path
+                .parse::<JsonPath>()
+                .with_context(|| format!("assert path: {}", assert.path))?;
+        }
+        if let Some(openapi) = &self.openapi {
+            openapi.validate().context("assert openapi")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn assert_check_should_work() {
+        let assert: AssertConfig = serde_yaml::from_str(
+            r#"
+status: 200
+max_latency_ms: 100
+headers:
+  content-type:
+    regex: ^application/json
+  x-trace-id:
+    exists: false
+body:
+  - path: $.id
+    eq: 1
+  - path: $.items[*].name
+    type: string
+  - path: $.title
+    regex: ^todo
+  - path: $.missing
+"#,
+        )
+        .unwrap();
+        assert.validate().unwrap();
+        let res = FilteredResponse {
+            status: "HTTP/1.1 200 OK".into(),
+            headers: [("content-type".to_string(), "application/json".to_string())]
+                .into_iter()
+                .collect(),
+            body: json!({"id": 2, "title": "todo 1", "items": [{"name": "a"}, {"name": 1}]}),
+        };
+        assert_eq!(
+            assert.check(500, &res, Duration::from_millis(120)),
+            vec![
+                "status: expected 200, got 500",
+                "latency: 120ms exceeds 100ms",
+                "$.id: expected 1, got 2",
+                "$.items[*].name: expected type string, got integer",
+                "$.missing: not found",
+            ]
+        );
+    }
+}
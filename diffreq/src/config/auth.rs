@@ -0,0 +1,478 @@
+use std::{
+    collections::BTreeMap,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hmac::{Hmac, Mac};
+use md5::Md5;
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION, CONTENT_TYPE, WWW_AUTHENTICATE},
+    Request,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use super::ConfigValidate;
+use crate::util::utc_datetime;
+
+// profile 的认证方式，在 send 中合并完 ExtraArgs 之后作用于最终的请求，签名覆盖最终的 query，header 和 body
+// ```yaml
+// auth:
+//   type: aws_sigv4
+//   access_key: ${AWS_ACCESS_KEY_ID}
+//   secret_key: ${AWS_SECRET_ACCESS_KEY}
+//   region: us-east-1
+//   service: execute-api
+// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum AuthConfig {
+    Basic {
+        username: String,
+        #[serde(default)]
+        password: String,
+    },
+    Bearer {
+        token: String,
+    },
+    /// api key in a header or a query parameter
+    ApiKey {
+        name: String,
+        value: String,
+        #[serde(rename = "in", default)]
+        location: ApiKeyLocation,
+    },
+    /// answer the digest challenge of the 401 response, then resend the request
+    Digest {
+        username: String,
+        password: String,
+    },
+    AwsSigv4 {
+        access_key: String,
+        secret_key: String,
+        #[serde(skip_serializing_if = "Option::is_none", default)]
+        session_token: Option<String>,
+        region: String,
+        service: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ApiKeyLocation {
+    #[default]
+    Header,
+    Query,
+}
+
+impl AuthConfig {
+    /// apply the auth to the final request, digest auth is applied after the challenge
+    pub(crate) fn apply(&self, req: &mut Request) -> Result<()> {
+        match self {
+            AuthConfig::Basic { username, password } => {
+                let credential = STANDARD.encode(format!("{}:{}", username, password));
+                let value = format!("Basic {}", credential);
+                req.headers_mut().insert(AUTHORIZATION, value.parse()?);
+            }
+            AuthConfig::Bearer { token } => {
+                let value = format!("Bearer {}", token);
+                req.headers_mut().insert(AUTHORIZATION, value.parse()?);
+            }
+            AuthConfig::ApiKey {
+                name,
+                value,
+                location: ApiKeyLocation::Header,
+            } => {
+                req.headers_mut()
+                    .insert(HeaderName::from_bytes(name.as_bytes())?, value.parse()?);
+            }
+            AuthConfig::ApiKey {
+                name,
+                value,
+                location: ApiKeyLocation::Query,
+            } => {
+                req.url_mut().query_pairs_mut().append_pair(name, value);
+            }
+            AuthConfig::Digest { .. } => {}
+            AuthConfig::AwsSigv4 { .. } => self.sign_aws_sigv4(req, SystemTime::now())?,
+        }
+        Ok(())
+    }
+
+    /// the authorization header answering the digest challenge of the 401 response
+    pub(crate) fn digest_authorization(
+        &self,
+        req: &Request,
+        res_headers: &HeaderMap,
+    ) -> Result<Option<HeaderValue>> {
+        let (username, password) = match self {
+            AuthConfig::Digest { username, password } => (username, password),
+            _ => return Ok(None),
+        };
+        let challenge = res_headers
+            .get_all(WWW_AUTHENTICATE)
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .find_map(|v| {
+                v.get(..7)
+                    .filter(|s| s.eq_ignore_ascii_case("digest "))
+                    .map(|_| parse_challenge(&v[7..]))
+            });
+        let challenge = match challenge {
+            Some(challenge) => challenge,
+            None => return Ok(None),
+        };
+        let uri = match req.url().query() {
+            Some(query) => format!("{}?{}", req.url().path(), query),
+            None => req.url().path().to_string(),
+        };
+        let value = digest_response(
+            username,
+            password,
+            &challenge,
+            req.method().as_str(),
+            &uri,
+            &gen_cnonce(),
+        )?;
+        Ok(Some(value.parse()?))
+    }
+
+    // https://docs.aws.amazon.com/general/latest/gr/sigv4-create-canonical-request.html
+    fn sign_aws_sigv4(&self, req: &mut Request, now: SystemTime) -> Result<()> {
+        let (access_key, secret_key, session_token, region, service) = match self {
+            AuthConfig::AwsSigv4 {
+                access_key,
+                secret_key,
+                session_token,
+                region,
+                service,
+            } => (access_key, secret_key, session_token, region, service),
+            _ => return Ok(()),
+        };
+        let (date, amz_date) = utc_date(now)?;
+        let payload = req.body().and_then(|b| b.as_bytes()).unwrap_or_default();
+        let payload_hash = hex(&Sha256::digest(payload));
+
+        let headers = req.headers_mut();
+        headers.insert("x-amz-date", amz_date.parse()?);
+        if let Some(token) = session_token {
+            headers.insert("x-amz-security-token", token.parse()?);
+        }
+        // s3 需要 x-amz-content-sha256
+        if service == "s3" {
+            headers.insert("x-amz-content-sha256", payload_hash.parse()?);
+        }
+
+        let url = req.url();
+        let host = match url.port() {
+            Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+            None => url.host_str().unwrap_or_default().to_string(),
+        };
+        // 签名 host，content-type 和所有的 x-amz-* header
+        let mut signed = BTreeMap::new();
+        signed.insert("host".to_string(), host);
+        for (k, v) in req.headers() {
+            if k == CONTENT_TYPE || k.as_str().starts_with("x-amz-") {
+                signed.insert(k.as_str().to_string(), v.to_str()?.trim().to_string());
+            }
+        }
+        let canonical_headers: String = signed
+            .iter()
+            .map(|(k, v)| format!("{}:{}\n", k, v))
+            .collect();
+        let signed_headers = signed.keys().cloned().collect::<Vec<_>>().join(";");
+
+        let mut query: Vec<_> = url
+            .query_pairs()
+            .map(|(k, v)| (aws_uri_encode(k.as_bytes()), aws_uri_encode(v.as_bytes())))
+            .collect();
+        query.sort();
+        let canonical_query = query
+            .iter()
+            .map(|(k, v)| format!("{}={}", k, v))
+            .collect::<Vec<_>>()
+            .join("&");
+        let canonical_request = format!(
+            "{}\n{}\n{}\n{}\n{}\n{}",
+            req.method(),
+            canonical_uri(url.path(), service),
+            canonical_query,
+            canonical_headers,
+            signed_headers,
+            payload_hash
+        );
+
+        let scope = format!("{}/{}/{}/aws4_request", date, region, service);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            hex(&Sha256::digest(canonical_request.as_bytes()))
+        );
+        let mut key = hmac_sha256(format!("AWS4{}", secret_key).as_bytes(), date.as_bytes())?;
+        for part in [region.as_str(), service.as_str(), "aws4_request"] {
+            key = hmac_sha256(&key, part.as_bytes())?;
+        }
+        let signature = hex(&hmac_sha256(&key, string_to_sign.as_bytes())?);
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            access_key, scope, signed_headers, signature
+        );
+        req.headers_mut()
+            .insert(AUTHORIZATION, authorization.parse()?);
+        Ok(())
+    }
+}
+
+impl ConfigValidate for AuthConfig {
+    fn validate(&self) -> Result<()> {
+        match self {
+            AuthConfig::ApiKey {
+                name,
+                location: ApiKeyLocation::Header,
+                ..
+            } => {
+                HeaderName::from_bytes(name.as_bytes())
+                    .map_err(|_| anyhow!("auth: invalid api key header: {}", name))?;
+            }
+            AuthConfig::AwsSigv4 {
+                region, service, ..
+            } if region.is_empty() || service.is_empty() => {
+                return Err(anyhow!("auth: aws_sigv4 requires region and service"));
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+}
+
+// `realm="a", qop="auth,auth-int", nonce="..."` 解析为 key value
+fn parse_challenge(s: &str) -> BTreeMap<String, String> {
+    let mut params = BTreeMap::new();
+    let mut rest = s.trim();
+    while let Some((key, value)) = rest.split_once('=') {
+        let key = key.trim().trim_start_matches(',').trim().to_lowercase();
+        let value = value.trim_start();
+        let (value, next) = match value.strip_prefix('"') {
+            Some(quoted) => {
+                let end = quoted.find('"').unwrap_or(quoted.len());
+                (&quoted[..end], quoted.get(end + 1..).unwrap_or_default())
+            }
+            None => {
+                let end = value.find(',').unwrap_or(value.len());
+                (value[..end].trim(), &value[end..])
+            }
+        };
+        params.insert(key, value.to_string());
+        rest = next;
+    }
+    params
+}
+
+// https://www.rfc-editor.org/rfc/rfc7616 支持 MD5，SHA-256 以及对应的 -sess 算法
+fn digest_response(
+    username: &str,
+    password: &str,
+    challenge: &BTreeMap<String, String>,
+    method: &str,
+    uri: &str,
+    cnonce: &str,
+) -> Result<String> {
+    let get = |k: &str| challenge.get(k).map(|s| s.as_str()).unwrap_or_default();
+    let (realm, nonce) = (get("realm"), get("nonce"));
+    let algorithm = challenge
+        .get("algorithm")
+        .map(|s| s.to_uppercase())
+        .unwrap_or_else(|| "MD5".to_string());
+    let hash: fn(&str) -> String = match algorithm.trim_end_matches("-SESS") {
+        "MD5" => |s| hex(&Md5::digest(s.as_bytes())),
+        "SHA-256" => |s| hex(&Sha256::digest(s.as_bytes())),
+        _ => return Err(anyhow!("Unsupported digest algorithm: {}", algorithm)),
+    };
+    let mut ha1 = hash(&format!("{}:{}:{}", username, realm, password));
+    if algorithm.ends_with("-SESS") {
+        ha1 = hash(&format!("{}:{}:{}", ha1, nonce, cnonce));
+    }
+    let ha2 = hash(&format!("{}:{}", method, uri));
+    // 只支持 qop=auth，没有 qop 时按 RFC 2069 计算，只提供 auth-int 时无法认证
+    let qop = match challenge.get("qop") {
+        Some(qop) if qop.split(',').any(|q| q.trim() == "auth") => Some(qop),
+        Some(qop) => return Err(anyhow!("Unsupported digest qop: {}", qop)),
+        None => None,
+    };
+    let nc = "00000001";
+    let response = match qop {
+        Some(_) => hash(&format!("{}:{}:{}:{}:auth:{}", ha1, nonce, nc, cnonce, ha2)),
+        None => hash(&format!("{}:{}:{}", ha1, nonce, ha2)),
+    };
+
+    let mut value = format!(
+        "Digest username=\"{}\", realm=\"{}\", nonce=\"{}\", uri=\"{}\", algorithm={}, response=\"{}\"",
+        username, realm, nonce, uri, algorithm, response
+    );
+    if qop.is_some() {
+        value.push_str(&format!(", qop=auth, nc={}, cnonce=\"{}\"", nc, cnonce));
+    }
+    if let Some(opaque) = challenge.get("opaque") {
+        value.push_str(&format!(", opaque=\"{}\"", opaque));
+    }
+    Ok(value)
+}
+
+fn gen_cnonce() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    let seed = format!("{}:{}", nanos, COUNTER.fetch_add(1, Ordering::Relaxed));
+    hex(&Md5::digest(seed.as_bytes()))[..16].to_string()
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Result<Vec<u8>> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key)?;
+    mac.update(data);
+    Ok(mac.finalize().into_bytes().to_vec())
+}
+
+fn hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+// aws 要求除了 `A-Za-z0-9-_.~` 之外的字符都需要编码，空格编码为 %20
+fn aws_uri_encode(s: &[u8]) -> String {
+    s.iter()
+        .map(|&b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            b => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+// url 中的 path 已经按 url 的规则编码过（比如 `:`、`(` 不编码），每个 segment 解码后按 aws 的规则重新编码，
+// s3 之外的服务需要编码两次
+fn canonical_uri(path: &str, service: &str) -> String {
+    if path.is_empty() {
+        return "/".to_string();
+    }
+    path.split('/')
+        .map(|segment| {
+            let encoded = aws_uri_encode(&percent_decode(segment));
+            if service == "s3" {
+                encoded
+            } else {
+                aws_uri_encode(encoded.as_bytes())
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn percent_decode(s: &str) -> Vec<u8> {
+    let bytes = s.as_bytes();
+    let mut output = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let decoded = (bytes[i] == b'%')
+            .then(|| s.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match decoded {
+            Some(b) => {
+                output.push(b);
+                i += 3;
+            }
+            None => {
+                output.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    output
+}
+
+// 返回 UTC 的 `YYYYMMDD` 和 `YYYYMMDDTHHMMSSZ`
+fn utc_date(time: SystemTime) -> Result<(String, String)> {
+    let secs = time.duration_since(UNIX_EPOCH)?.as_secs() as i64;
+    let (year, month, day, hour, minute, second) = utc_datetime(secs);
+    let date = format!("{:04}{:02}{:02}", year, month, day);
+    let time = format!("{}T{:02}{:02}{:02}Z", date, hour, minute, second);
+    Ok((date, time))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use url::Url;
+
+    #[test]
+    fn aws_sigv4_should_match_aws_example() {
+        // aws 文档中的示例请求
+        let url =
+            Url::parse("https://iam.amazonaws.com/?Action=ListUsers&Version=2010-05-08").unwrap();
+        let mut req = Request::new(reqwest::Method::GET, url);
+        req.headers_mut().insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("application/x-www-form-urlencoded; charset=utf-8"),
+        );
+        let auth = AuthConfig::AwsSigv4 {
+            access_key: "AKIDEXAMPLE".into(),
+            secret_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".into(),
+            session_token: None,
+            region: "us-east-1".into(),
+            service: "iam".into(),
+        };
+        let now = UNIX_EPOCH + Duration::from_secs(1440938160);
+        auth.sign_aws_sigv4(&mut req, now).unwrap();
+        assert_eq!(req.headers()["x-amz-date"], "20150830T123600Z");
+        assert_eq!(
+            req.headers()[AUTHORIZATION],
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/iam/aws4_request, \
+             SignedHeaders=content-type;host;x-amz-date, \
+             Signature=5d672d79c15b13162d9279b0855cfba6789a8edb4c82c400e06b5924a6f2b5d7"
+        );
+    }
+
+    #[test]
+    fn digest_response_should_match_rfc_example() {
+        // RFC 2617 3.5 中的示例
+        let challenge = parse_challenge(
+            "realm=\"testrealm@host.com\", qop=\"auth,auth-int\", \
+             nonce=\"dcd98b7102dd2f0e8b11d0f600bfb0c093\", opaque=\"5ccc069c403ebaf9f0171e9517f40e41\"",
+        );
+        let value = digest_response(
+            "Mufasa",
+            "Circle Of Life",
+            &challenge,
+            "GET",
+            "/dir/index.html",
+            "0a4f113b",
+        )
+        .unwrap();
+        assert!(value.contains("response=\"6629fae49393a05397450978507c4ef1\""));
+        assert!(value.contains("opaque=\"5ccc069c403ebaf9f0171e9517f40e41\""));
+
+        let challenge = parse_challenge("realm=\"a\", qop=\"auth-int\", nonce=\"n\"");
+        assert!(digest_response("u", "p", &challenge, "GET", "/", "c").is_err());
+    }
+
+    #[test]
+    fn canonical_uri_should_encode_each_segment() {
+        let url = Url::parse("https://example.com/a b/c%2Fd/it's(1)/é").unwrap();
+        assert_eq!(
+            canonical_uri(url.path(), "s3"),
+            "/a%20b/c%2Fd/it%27s%281%29/%C3%A9"
+        );
+        assert_eq!(
+            canonical_uri(url.path(), "execute-api"),
+            "/a%2520b/c%252Fd/it%2527s%25281%2529/%25C3%25A9"
+        );
+        assert_eq!(canonical_uri("", "s3"), "/");
+    }
+}
@@ -0,0 +1,178 @@
+use std::{path::Path, sync::Arc, time::Duration};
+
+use anyhow::{anyhow, Context, Result};
+use reqwest::{redirect::Policy, Certificate, Client, Proxy};
+use serde::{Deserialize, Serialize};
+use url::Url;
+
+use super::{cookie::FileCookieJar, is_default, ConfigValidate};
+
+// http client 的配置，一次运行中所有的请求共用同一个 client，以复用连接
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq, Eq)]
+pub struct ClientConfig {
+    // 整个请求的超时时间，单位为毫秒
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub timeout_ms: Option<u64>,
+    // 建立连接的超时时间，单位为毫秒
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub connect_timeout_ms: Option<u64>,
+    // 最多跟随的重定向次数，0 表示不跟随重定向，默认为 10 次
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub max_redirects: Option<usize>,
+    // 代理地址，比如：http://127.0.0.1:8080, socks5 需要开启 reqwest 的 socks feature
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub proxy: Option<String>,
+    // 自定义的 CA 证书（PEM 格式）文件路径
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub ca_cert: Option<String>,
+    // 不校验服务端证书，仅用于测试环境
+    #[serde(skip_serializing_if = "is_default", default)]
+    pub insecure: bool,
+    // 使用的 http 版本，默认通过 TLS 的 ALPN 协商，服务端支持时优先使用 HTTP/2
+    #[serde(skip_serializing_if = "is_default", default)]
+    pub http_version: HttpVersion,
+    // cookie jar 文件路径，记录响应的 Set-Cookie，之后的请求（包括之后的运行）会带上匹配的 cookie
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub cookie_jar: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HttpVersion {
+    /// negotiated by ALPN, HTTP/2 is preferred over TLS, plain http uses HTTP/1.1
+    #[default]
+    Auto,
+    /// HTTP/1.1 only
+    Http1,
+    /// forced HTTP/2 with prior knowledge (h2c for plain http), HTTP/1.1 only servers fail
+    Http2,
+}
+
+impl ClientConfig {
+    /// resolve the relative file paths against the dir of the config file
+    pub fn resolve_paths(&mut self, dir: &Path) {
+        for path in [&mut self.ca_cert, &mut self.cookie_jar]
+            .into_iter()
+            .flatten()
+        {
+            *path = dir.join(&*path).to_string_lossy().into_owned();
+        }
+    }
+
+    /// build a reqwest client from the config
+    pub fn build(&self) -> Result<Client> {
+        let mut builder = Client::builder();
+        if let Some(timeout) = self.timeout_ms {
+            builder = builder.timeout(Duration::from_millis(timeout));
+        }
+        if let Some(timeout) = self.connect_timeout_ms {
+            builder = builder.connect_timeout(Duration::from_millis(timeout));
+        }
+        if let Some(max) = self.max_redirects {
+            builder = builder.redirect(match max {
+                0 => Policy::none(),
+                n => Policy::limited(n),
+            });
+        }
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(Proxy::all(proxy.as_str())?);
+        }
+        if let Some(path) = &self.ca_cert {
+            let pem = std::fs::read(path).with_context(|| format!("read ca_cert: {}", path))?;
+            builder = builder.add_root_certificate(Certificate::from_pem(&pem)?);
+        }
+        if self.insecure {
+            builder = builder.danger_accept_invalid_certs(true);
+        }
+        match self.http_version {
+            HttpVersion::Auto => {}
+            HttpVersion::Http1 => builder = builder.http1_only(),
+            HttpVersion::Http2 => builder = builder.http2_prior_knowledge(),
+        }
+        if let Some(path) = &self.cookie_jar {
+            builder = builder.cookie_provider(Arc::new(FileCookieJar::load(path)?));
+        }
+        Ok(builder.build()?)
+    }
+}
+
+impl ConfigValidate for ClientConfig {
+    fn validate(&self) -> Result<()> {
+        if let Some(proxy) = &self.proxy {
+            Url::parse(proxy).with_context(|| format!("invalid proxy: {}", proxy))?;
+        }
+        if let Some(path) = &self.ca_cert {
+            if !Path::new(path).is_file() {
+                return Err(anyhow!("ca_cert: {} is not a file", path));
+            }
+        }
+        if let Some(path) = &self.cookie_jar {
+            if Path::new(path).is_dir() {
+                return Err(anyhow!("cookie_jar: {} is a directory", path));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::HttpVersion;
+    use crate::{ConfigLoad, RequestConfig};
+
+    #[test]
+    fn client_config_should_be_loaded_with_profiles() {
+        let content = r#"
+client:
+  timeout_ms: 1000
+  max_redirects: 0
+  insecure: true
+  http_version: http1
+todo:
+  url: https://jsonplaceholder.typicode.com/todos/1
+"#;
+        let config = RequestConfig::from_yaml(content).unwrap();
+        assert_eq!(config.client.timeout_ms, Some(1000));
+        assert_eq!(config.client.max_redirects, Some(0));
+        assert!(config.client.insecure);
+        assert_eq!(config.client.http_version, HttpVersion::Http1);
+        assert_eq!(config.profiles.len(), 1);
+        assert!(config.client.build().is_ok());
+    }
+
+    #[test]
+    fn client_ca_cert_should_be_relative_to_config_dir() {
+        let (dir, path) = crate::util::temp_file("ca.pem", "");
+        let content = r#"
+client:
+  ca_cert: ca.pem
+todo:
+  url: https://jsonplaceholder.typicode.com/todos/1
+"#;
+        let config =
+            RequestConfig::from_yaml_in_dir(content, &Default::default(), dir.path()).unwrap();
+        assert_eq!(
+            config.client.ca_cert,
+            Some(path.to_string_lossy().into_owned())
+        );
+        assert!(RequestConfig::from_yaml(content).is_err());
+    }
+
+    #[test]
+    fn client_cookie_jar_should_be_relative_to_config_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let content = r#"
+client:
+  cookie_jar: cookies.json
+todo:
+  url: https://jsonplaceholder.typicode.com/todos/1
+"#;
+        let config =
+            RequestConfig::from_yaml_in_dir(content, &Default::default(), dir.path()).unwrap();
+        let path = dir.path().join("cookies.json");
+        assert_eq!(
+            config.client.cookie_jar,
+            Some(path.to_string_lossy().into_owned())
+        );
+    }
+}
@@ -0,0 +1,103 @@
+use std::{
+    fs::File,
+    io::BufReader,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use anyhow::{anyhow, Context, Result};
+use cookie_store::{CookieStore, RawCookie};
+use reqwest::header::HeaderValue;
+use url::Url;
+
+// 持久化到文件的 cookie jar，client 创建时从文件加载，收到 `Set-Cookie` 时写回文件，
+// 会话 cookie（没有过期时间的）也会保存，这样多次运行之间可以保持登录状态
+#[derive(Debug)]
+pub struct FileCookieJar {
+    path: PathBuf,
+    store: Mutex<CookieStore>,
+}
+
+impl FileCookieJar {
+    /// load the cookies from the file, the file is created on the first `Set-Cookie`
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let store = if path.is_file() {
+            let file = File::open(&path)
+                .with_context(|| format!("open cookie jar: {}", path.display()))?;
+            CookieStore::load_json(BufReader::new(file))
+                .map_err(|e| anyhow!("load cookie jar {}: {}", path.display(), e))?
+        } else {
+            CookieStore::default()
+        };
+        Ok(Self {
+            path,
+            store: Mutex::new(store),
+        })
+    }
+
+    fn save(&self, store: &CookieStore) -> Result<()> {
+        if let Some(dir) = self.path.parent().filter(|d| !d.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut file = File::create(&self.path)
+            .with_context(|| format!("create cookie jar: {}", self.path.display()))?;
+        store
+            .save_incl_expired_and_nonpersistent_json(&mut file)
+            .map_err(|e| anyhow!("save cookie jar {}: {}", self.path.display(), e))
+    }
+}
+
+impl reqwest::cookie::CookieStore for FileCookieJar {
+    fn set_cookies(&self, cookie_headers: &mut dyn Iterator<Item = &HeaderValue>, url: &Url) {
+        let cookies: Vec<_> = cookie_headers
+            .filter_map(|v| v.to_str().ok())
+            .filter_map(|v| RawCookie::parse(v.to_string()).ok())
+            .collect();
+        if cookies.is_empty() {
+            return;
+        }
+        let mut store = self.store.lock().unwrap();
+        store.store_response_cookies(cookies.into_iter(), url);
+        // 写文件失败不影响请求本身
+        if let Err(e) = self.save(&store) {
+            eprintln!("Warning: {:#}", e);
+        }
+    }
+
+    fn cookies(&self, url: &Url) -> Option<HeaderValue> {
+        let store = self.store.lock().unwrap();
+        let cookie = store
+            .get_request_values(url)
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect::<Vec<_>>()
+            .join("; ");
+        if cookie.is_empty() {
+            None
+        } else {
+            HeaderValue::from_str(&cookie).ok()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::cookie::CookieStore as _;
+
+    #[test]
+    fn cookie_jar_should_persist_cookies() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cookies.json");
+        let url = Url::parse("https://example.com/login").unwrap();
+        let jar = FileCookieJar::load(&path).unwrap();
+        let set_cookie = HeaderValue::from_static("PHPSESSID=abc; Path=/; HttpOnly");
+        jar.set_cookies(&mut [&set_cookie].into_iter(), &url);
+
+        let jar = FileCookieJar::load(&path).unwrap();
+        let other = Url::parse("https://example.com/api/todos").unwrap();
+        assert_eq!(jar.cookies(&other).unwrap(), "PHPSESSID=abc");
+        let other = Url::parse("https://example.org/").unwrap();
+        assert!(jar.cookies(&other).is_none());
+    }
+}
@@ -0,0 +1,474 @@
+use std::str::FromStr;
+
+use anyhow::{anyhow, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION, CONTENT_TYPE, COOKIE, RANGE},
+    Method,
+};
+use serde_json::json;
+
+use super::{decoder::decode_form, get_content_type, parse_query_value, RequestProfile};
+
+// 这些参数后面需要跟一个值，但是对生成 profile 没有影响，直接忽略
+const IGNORED_WITH_VALUE: &[&str] = &[
+    "-o",
+    "--output",
+    "-m",
+    "--max-time",
+    "--connect-timeout",
+    "-x",
+    "--proxy",
+    "--retry",
+    "--retry-delay",
+    "--retry-max-time",
+    "-w",
+    "--write-out",
+    "--cacert",
+    "--capath",
+    "-E",
+    "--cert",
+    "--key",
+    "-c",
+    "--cookie-jar",
+    "-D",
+    "--dump-header",
+    "--limit-rate",
+    "--max-redirs",
+    "-y",
+    "--speed-time",
+    "-Y",
+    "--speed-limit",
+    "--stderr",
+    "--trace",
+    "--trace-ascii",
+];
+
+// 不带值的参数，对生成 profile 没有影响，直接忽略
+const IGNORED_FLAGS: &[&str] = &[
+    "-s",
+    "--silent",
+    "-S",
+    "--show-error",
+    "-L",
+    "--location",
+    "--location-trusted",
+    "-k",
+    "--insecure",
+    "-v",
+    "--verbose",
+    "-i",
+    "--include",
+    "-f",
+    "--fail",
+    "--fail-with-body",
+    "-g",
+    "--globoff",
+    "-N",
+    "--no-buffer",
+    "-#",
+    "--progress-bar",
+    "--no-progress-meter",
+    "--compressed",
+    "--http1.1",
+    "--http2",
+    "--http2-prior-knowledge",
+    "--no-keepalive",
+    "--path-as-is",
+];
+
+// 需要一个值的短参数，值可以直接跟在参数后面，比如 `-XPOST`, `-HAccept:x`
+const SHORT_WITH_VALUE: &[&str] = &["-X", "-H", "-d", "-F", "-u", "-b", "-A", "-e", "-r"];
+
+/// parse a curl command line (e.g. copied from browser devtools) into a request profile
+pub fn parse_curl(cmd: &str) -> Result<RequestProfile> {
+    let args = split_shell_words(cmd)?;
+    let mut args = args.into_iter();
+    match args.next().as_deref() {
+        Some("curl") => {}
+        _ => return Err(anyhow!("Not a curl command: {}", cmd)),
+    }
+    let args = expand_short_options(args)?;
+    let mut args = args.into_iter();
+
+    let mut url = None;
+    let mut method = None;
+    let mut headers = HeaderMap::new();
+    let mut data: Vec<String> = vec![];
+    let mut form = serde_json::Map::new();
+    let mut get = false;
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .ok_or_else(|| anyhow!("Missing value for curl option: {}", name))
+        };
+        match arg.as_str() {
+            "-X" | "--request" => method = Some(Method::from_str(&value(&arg)?.to_uppercase())?),
+            "-H" | "--header" => {
+                let header = value(&arg)?;
+                let (k, v) = header
+                    .split_once(':')
+                    .ok_or_else(|| anyhow!("Invalid header: {}", header))?;
+                headers.append(HeaderName::from_str(k.trim())?, v.trim().parse()?);
+            }
+            "-d" | "--data" | "--data-binary" | "--data-ascii" => {
+                data.push(read_data(&arg, value(&arg)?)?)
+            }
+            "--data-raw" => data.push(value(&arg)?),
+            "--data-urlencode" => data.push(urlencode_data(&value(&arg)?)?),
+            "--json" => {
+                data.push(read_data(&arg, value(&arg)?)?);
+                headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+            }
+            "-F" | "--form" => {
+                let (k, v) = split_form_field(&value(&arg)?)?;
+                form.insert(k, v.into());
+            }
+            "--form-string" => {
+                // 值按原样发送，`@` 开头的值也不会读取文件
+                let (k, v) = split_form_field(&value(&arg)?)?;
+                let v = match v.starts_with('@') {
+                    true => json!({ "value": v }),
+                    false => v.into(),
+                };
+                form.insert(k, v);
+            }
+            "-u" | "--user" => {
+                let auth = format!("Basic {}", STANDARD.encode(value(&arg)?));
+                headers.insert(AUTHORIZATION, auth.parse()?);
+            }
+            "-b" | "--cookie" => {
+                // 不带 `=` 的是 cookie 文件，忽略
+                let cookie = value(&arg)?;
+                if cookie.contains('=') {
+                    headers.append(COOKIE, cookie.parse()?);
+                }
+            }
+            "-A" | "--user-agent" => {
+                headers.insert("user-agent", value(&arg)?.parse()?);
+            }
+            "-e" | "--referer" => {
+                headers.insert("referer", value(&arg)?.parse()?);
+            }
+            "-r" | "--range" => {
+                headers.insert(RANGE, format!("bytes={}", value(&arg)?).parse()?);
+            }
+            "-G" | "--get" => get = true,
+            "-I" | "--head" => method = Some(Method::HEAD),
+            "--url" => url = Some(value(&arg)?),
+            s if IGNORED_WITH_VALUE.contains(&s) => {
+                value(&arg)?;
+            }
+            s if IGNORED_FLAGS.contains(&s) => {}
+            // 不认识的参数可能需要一个值，不能把它的值当作 url
+            s if s.starts_with('-') && s.len() > 1 => {
+                return Err(anyhow!("Unsupported curl option: {}", s))
+            }
+            _ => url = Some(arg),
+        }
+    }
+
+    let url = url.ok_or_else(|| anyhow!("Missing url in curl command"))?;
+    let mut profile: RequestProfile = url.parse()?;
+    let data = data.join("&");
+    let has_body = !data.is_empty() || !form.is_empty();
+    profile.method = method.unwrap_or(if has_body && !get {
+        Method::POST
+    } else {
+        Method::GET
+    });
+
+    if get {
+        // -G 表示 data 作为 query 参数发送
+        let params = profile.params.get_or_insert_with(|| json!({}));
+        for (k, v) in url::form_urlencoded::parse(data.as_bytes()) {
+            params[&*k] = parse_query_value(&v);
+        }
+    } else if !form.is_empty() {
+        headers
+            .entry(CONTENT_TYPE)
+            .or_insert(HeaderValue::from_static("multipart/form-data"));
+        profile.body = Some(serde_json::Value::Object(form));
+    } else if !data.is_empty() {
+        headers
+            .entry(CONTENT_TYPE)
+            .or_insert(HeaderValue::from_static(
+                "application/x-www-form-urlencoded",
+            ));
+        profile.body = Some(data_to_body(&data, get_content_type(&headers).as_deref()));
+    }
+    profile.headers = headers;
+    Ok(profile)
+}
+
+fn split_form_field(field: &str) -> Result<(String, String)> {
+    field
+        .split_once('=')
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .ok_or_else(|| anyhow!("Invalid form field: {}", field))
+}
+
+// 根据 content type 把 data 转换为 profile 中的 body，form 中同名的 key 合并为数组
+fn data_to_body(data: &str, content_type: Option<&str>) -> serde_json::Value {
+    match content_type {
+        Some("application/json") => serde_json::from_str(data).unwrap_or_else(|_| data.into()),
+        Some("application/x-www-form-urlencoded") => decode_form(data.as_bytes()),
+        _ => data.into(),
+    }
+}
+
+// 把 `-XPOST` 这种值直接跟在后面的短参数拆开，`-sSL` 这种组合的短参数拆成单独的参数
+fn expand_short_options(args: impl Iterator<Item = String>) -> Result<Vec<String>> {
+    let mut expanded = vec![];
+    for arg in args {
+        if arg.starts_with("--") || !arg.starts_with('-') || arg.chars().count() <= 2 {
+            expanded.push(arg);
+            continue;
+        }
+        let (opt, rest) = arg.split_at(2);
+        if SHORT_WITH_VALUE.contains(&opt) || IGNORED_WITH_VALUE.contains(&opt) {
+            expanded.push(opt.to_string());
+            expanded.push(rest.to_string());
+            continue;
+        }
+        let flags: Vec<String> = arg[1..].chars().map(|c| format!("-{}", c)).collect();
+        match flags.iter().all(|f| IGNORED_FLAGS.contains(&f.as_str())) {
+            true => expanded.extend(flags),
+            false => return Err(anyhow!("Unsupported curl option: {}", arg)),
+        }
+    }
+    Ok(expanded)
+}
+
+// `@file` 表示从文件中读取 data，除了 --data-binary 以外会去掉文件中的换行
+fn read_data(opt: &str, data: String) -> Result<String> {
+    match data.strip_prefix('@') {
+        Some("-") => Err(anyhow!("Reading {} from stdin is not supported", opt)),
+        Some(path) => {
+            let content = std::fs::read_to_string(path)
+                .with_context(|| format!("read {} file: {}", opt, path))?;
+            Ok(match opt {
+                "--data-binary" => content,
+                _ => content.replace(['\r', '\n'], ""),
+            })
+        }
+        None => Ok(data),
+    }
+}
+
+// --data-urlencode 的几种写法：`content`, `=content`, `name=content`, `@file`, `name@file`
+fn urlencode_data(data: &str) -> Result<String> {
+    let encode = |s: &str| url::form_urlencoded::byte_serialize(s.as_bytes()).collect::<String>();
+    let read = |path: &str| {
+        std::fs::read_to_string(path)
+            .with_context(|| format!("read --data-urlencode file: {}", path))
+    };
+    let eq = data.find('=');
+    let at = data.find('@');
+    Ok(match (eq, at) {
+        (Some(i), at) if at.is_none_or(|at| i < at) => match data.split_at(i) {
+            ("", v) => encode(&v[1..]),
+            (k, v) => format!("{}={}", k, encode(&v[1..])),
+        },
+        (_, Some(0)) => encode(&read(&data[1..])?),
+        (_, Some(i)) => format!("{}={}", &data[..i], encode(&read(&data[i + 1..])?)),
+        _ => encode(data),
+    })
+}
+
+// 简单的 shell 分词，支持单引号，双引号，`$'...'`，反斜杠转义和续行
+fn split_shell_words(s: &str) -> Result<Vec<String>> {
+    let mut words = vec![];
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            '\\' => match chars.next() {
+                Some('\n') => {}
+                Some('\r') if chars.peek() == Some(&'\n') => {
+                    chars.next();
+                }
+                Some(c) => {
+                    word.push(c);
+                    in_word = true;
+                }
+                None => {}
+            },
+            '\'' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some(c) => word.push(c),
+                        None => return Err(anyhow!("Unclosed single quote")),
+                    }
+                }
+            }
+            '$' if chars.peek() == Some(&'\'') => {
+                chars.next();
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('\'') => break,
+                        Some('\\') => match chars.next() {
+                            Some('n') => word.push('\n'),
+                            Some('t') => word.push('\t'),
+                            Some('r') => word.push('\r'),
+                            Some(c) => word.push(c),
+                            None => return Err(anyhow!("Unclosed $' quote")),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err(anyhow!("Unclosed $' quote")),
+                    }
+                }
+            }
+            '"' => {
+                in_word = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some(c @ ('"' | '\\' | '$' | '`')) => word.push(c),
+                            Some('\n') => {}
+                            Some(c) => {
+                                word.push('\\');
+                                word.push(c);
+                            }
+                            None => return Err(anyhow!("Unclosed double quote")),
+                        },
+                        Some(c) => word.push(c),
+                        None => return Err(anyhow!("Unclosed double quote")),
+                    }
+                }
+            }
+            c => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+impl RequestProfile {
+    /// check whether the given text is a curl command instead of a url
+    pub fn is_curl(s: &str) -> bool {
+        s.trim_start().starts_with("curl ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::temp_file;
+
+    #[test]
+    fn split_shell_words_should_work() {
+        let words = split_shell_words(
+            "curl 'https://a.com/x' \\\n  -H \"a: \\\"b\\\"\" --data-raw $'{\"k\":\"it\\'s\"}'",
+        )
+        .unwrap();
+        assert_eq!(
+            words,
+            vec![
+                "curl",
+                "https://a.com/x",
+                "-H",
+                "a: \"b\"",
+                "--data-raw",
+                "{\"k\":\"it's\"}"
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_curl_should_work() {
+        let profile = parse_curl(
+            "curl 'https://a.com/api?page=1&q=rust' -H 'Content-Type: application/json' \
+             -H 'Cookie: a=1' -u user:pass --data-raw '{\"name\":\"todo\"}' --compressed",
+        )
+        .unwrap();
+        assert_eq!(profile.method, Method::POST);
+        assert_eq!(profile.url.as_str(), "https://a.com/api");
+        assert_eq!(profile.params, Some(json!({"page": 1, "q": "rust"})));
+        assert_eq!(profile.headers["cookie"], "a=1");
+        assert_eq!(profile.headers[AUTHORIZATION], "Basic dXNlcjpwYXNz");
+        assert_eq!(profile.body, Some(json!({"name": "todo"})));
+
+        let profile =
+            parse_curl("curl https://a.com/api -H 'content-type: application/json' -d '[1,2]'")
+                .unwrap();
+        assert_eq!(profile.body, Some(json!([1, 2])));
+        let profile = parse_curl("curl https://a.com/api -d a=1 -d a=2 -d b=3").unwrap();
+        assert_eq!(profile.body, Some(json!({"a": ["1", "2"], "b": "3"})));
+
+        let profile = parse_curl(
+            "curl -G https://a.com/api --data-urlencode 'q=hello world' -d page=2 -X PUT",
+        )
+        .unwrap();
+        assert_eq!(profile.method, Method::PUT);
+        assert_eq!(profile.params, Some(json!({"q": "hello world", "page": 2})));
+        assert_eq!(profile.body, None);
+
+        let profile = parse_curl("curl https://a.com/upload -F name=a -F file=@./a.txt").unwrap();
+        assert_eq!(profile.method, Method::POST);
+        assert_eq!(profile.headers[CONTENT_TYPE], "multipart/form-data");
+        assert_eq!(profile.body, Some(json!({"name": "a", "file": "@./a.txt"})));
+        let profile =
+            parse_curl("curl https://a.com/upload --form-string 'name=@a' --form-string tag=b")
+                .unwrap();
+        assert_eq!(
+            profile.body,
+            Some(json!({"name": {"value": "@a"}, "tag": "b"}))
+        );
+
+        let profile = parse_curl(
+            "curl -sSL -XPUT -HAccept:text/plain --limit-rate 1k -r 0-1 https://a.com/x",
+        )
+        .unwrap();
+        assert_eq!(profile.method, Method::PUT);
+        assert_eq!(profile.url.as_str(), "https://a.com/x");
+        assert_eq!(profile.headers["accept"], "text/plain");
+        assert_eq!(profile.headers[RANGE], "bytes=0-1");
+
+        let err = parse_curl("curl --resolve a.com:443:127.0.0.1 https://a.com/x").unwrap_err();
+        assert_eq!(err.to_string(), "Unsupported curl option: --resolve");
+        assert!(parse_curl("curl -K ./curlrc https://a.com/x").is_err());
+    }
+
+    #[test]
+    fn parse_curl_should_read_data_files() {
+        let (_dir, path) = temp_file("body.json", "{\"name\":\n\"todo\"}\n");
+        let cmd = format!(
+            "curl https://a.com/x -H 'content-type: application/json' -d @{}",
+            path.display()
+        );
+        let profile = parse_curl(&cmd).unwrap();
+        assert_eq!(profile.body, Some(json!({"name": "todo"})));
+
+        let cmd = format!(
+            "curl https://a.com/x -H 'content-type: text/plain' --data-binary @{}",
+            path.display()
+        );
+        let profile = parse_curl(&cmd).unwrap();
+        assert_eq!(profile.body, Some(json!("{\"name\":\n\"todo\"}\n")));
+
+        let cmd = format!(
+            "curl https://a.com/x -H 'content-type: text/plain' --data-raw @{}",
+            path.display()
+        );
+        let profile = parse_curl(&cmd).unwrap();
+        assert_eq!(profile.body, Some(json!(format!("@{}", path.display()))));
+    }
+}
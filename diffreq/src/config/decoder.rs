@@ -0,0 +1,481 @@
+use std::borrow::Cow;
+
+use anyhow::{anyhow, Context, Result};
+use encoding_rs::{Encoding, UTF_8};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+
+use super::encoder::get_encoder;
+
+// 根据响应的 content-type 解码 body：
+// - 文本按 content-type 中的 charset 解码，没有指定时按 utf8 解码
+// - json / yaml / ndjson / form / msgpack 解码为 json value，skip_body 的 json path 同样生效
+// - xml / html 格式化为缩进的文本，去掉多余的空白，方便逐行 diff
+// - 二进制的 body 小于 HEX_DUMP_LIMIT 时输出 hex dump，否则输出长度和 sha256
+const HEX_DUMP_LIMIT: usize = 512;
+const HTML_VOID_TAGS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track",
+    "wbr",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BodyKind {
+    Json,
+    Yaml,
+    Ndjson,
+    Form,
+    Msgpack,
+    Xml,
+    Html,
+    Text,
+    Binary,
+}
+
+fn body_kind(content_type: Option<&str>) -> BodyKind {
+    let content_type = match content_type {
+        Some(ct) => ct,
+        None => return BodyKind::Text,
+    };
+    match content_type {
+        "application/json" => BodyKind::Json,
+        "application/yaml" | "application/x-yaml" | "text/yaml" => BodyKind::Yaml,
+        "application/x-ndjson" => BodyKind::Ndjson,
+        "application/x-www-form-urlencoded" => BodyKind::Form,
+        "application/msgpack" | "application/x-msgpack" => BodyKind::Msgpack,
+        "text/html" | "application/xhtml+xml" => BodyKind::Html,
+        "application/xml" | "text/xml" => BodyKind::Xml,
+        ct if ct.ends_with("+json") => BodyKind::Json,
+        ct if ct.ends_with("+xml") => BodyKind::Xml,
+        ct if ct.ends_with("+yaml") => BodyKind::Yaml,
+        ct if ct.starts_with("text/") => BodyKind::Text,
+        "application/octet-stream" | "application/pdf" | "application/zip" | "application/gzip" => {
+            BodyKind::Binary
+        }
+        ct if ["image/", "audio/", "video/", "font/"]
+            .iter()
+            .any(|p| ct.starts_with(p)) =>
+        {
+            BodyKind::Binary
+        }
+        // 未知的类型，是 utf8 的按文本处理，否则按二进制处理
+        _ => BodyKind::Text,
+    }
+}
+
+// content-type 的 mime 类型（小写）和 charset 参数
+fn parse_content_type(content_type: Option<&str>) -> (Option<String>, Option<&str>) {
+    let content_type = match content_type {
+        Some(ct) => ct,
+        None => return (None, None),
+    };
+    let mut parts = content_type.split(';');
+    let mime = parts.next().map(|m| m.trim().to_lowercase());
+    let charset = parts.find_map(|p| {
+        let (k, v) = p.split_once('=')?;
+        k.trim()
+            .eq_ignore_ascii_case("charset")
+            .then(|| v.trim().trim_matches('"'))
+    });
+    (mime, charset)
+}
+
+// 按 charset 解码文本，未知的 charset 按 utf8 处理，有无法解码的字节时返回 None
+fn decode_text<'a>(data: &'a [u8], charset: Option<&str>) -> Option<Cow<'a, str>> {
+    let encoding = charset
+        .and_then(|label| Encoding::for_label(label.as_bytes()))
+        .unwrap_or(UTF_8);
+    let (text, _, had_errors) = encoding.decode(data);
+    (!had_errors).then_some(text)
+}
+
+/// the syntax (file extension) used to highlight the decoded body,
+/// `content_type` is the value of the content-type header
+pub fn body_syntax(content_type: Option<&str>) -> &'static str {
+    let (mime, _) = parse_content_type(content_type);
+    match body_kind(mime.as_deref()) {
+        BodyKind::Json | BodyKind::Yaml | BodyKind::Ndjson | BodyKind::Form | BodyKind::Msgpack => {
+            "json"
+        }
+        BodyKind::Xml => "xml",
+        BodyKind::Html => "html",
+        BodyKind::Text | BodyKind::Binary => "txt",
+    }
+}
+
+/// decode the response body by the value of the content-type header, structured bodies are
+/// decoded to json value, others are decoded to a string value by the charset
+pub fn decode_body(content_type: Option<&str>, data: &[u8]) -> Result<Value> {
+    let (mime, charset) = parse_content_type(content_type);
+    let kind = body_kind(mime.as_deref());
+    let text = || {
+        decode_text(data, charset)
+            .ok_or_else(|| anyhow!("invalid {} text", charset.unwrap_or("utf-8")))
+    };
+    // 结构化的 body 为空时（比如 204 或者 HEAD 的响应）没有内容可以解码，文本保持为空字符串
+    if data.is_empty()
+        && matches!(
+            kind,
+            BodyKind::Json | BodyKind::Yaml | BodyKind::Ndjson | BodyKind::Form | BodyKind::Msgpack
+        )
+    {
+        return Ok(Value::Null);
+    }
+    let value = match kind {
+        BodyKind::Json => serde_json::from_str(&text()?).context("decode json body")?,
+        BodyKind::Yaml => serde_yaml::from_str(&text()?).context("decode yaml body")?,
+        BodyKind::Ndjson => {
+            let text = text().context("decode ndjson body")?;
+            let items: Result<Vec<Value>, _> = text
+                .lines()
+                .filter(|line| !line.trim().is_empty())
+                .map(serde_json::from_str)
+                .collect();
+            Value::Array(items.context("decode ndjson body")?)
+        }
+        BodyKind::Form => decode_form(data),
+        BodyKind::Msgpack => rmp_serde::from_slice(data).context("decode msgpack body")?,
+        BodyKind::Xml | BodyKind::Html => match text() {
+            Ok(text) => Value::String(pretty_markup(&text, kind == BodyKind::Html)),
+            Err(_) => Value::String(binary_summary(data)),
+        },
+        // 包含 `\0` 的也当作二进制处理
+        BodyKind::Text => match text() {
+            Ok(text) if !text.contains('\0') => Value::String(text.into_owned()),
+            _ => Value::String(binary_summary(data)),
+        },
+        BodyKind::Binary => Value::String(binary_summary(data)),
+    };
+    Ok(value)
+}
+
+/// encode the decoded body again to replay it, returns the new content type if it changes:
+/// structured bodies are encoded in the recorded format, text is encoded as utf-8, binary
+/// bodies are only kept as a summary and can't be replayed
+pub fn encode_decoded(
+    content_type: Option<&str>,
+    body: Value,
+) -> Result<(Option<String>, Vec<u8>)> {
+    let (mime, charset) = parse_content_type(content_type);
+    let kind = body_kind(mime.as_deref());
+    match (kind, body) {
+        (_, Value::Null) => Ok((None, vec![])),
+        (_, Value::String(text)) if text.is_empty() => Ok((None, vec![])),
+        (BodyKind::Xml | BodyKind::Html | BodyKind::Text, Value::String(text))
+            if !is_binary_summary(&text) =>
+        {
+            // 解码之后的文本按 utf8 发送
+            Ok((utf8_content_type(mime, charset), text.into_bytes()))
+        }
+        (BodyKind::Xml | BodyKind::Html | BodyKind::Text | BodyKind::Binary, _) => Err(anyhow!(
+            "binary body ({}) is only recorded as a summary",
+            content_type.unwrap_or("no content-type")
+        )),
+        // json / yaml / ndjson / form / msgpack 使用对应的 encoder 按原来的格式编码
+        (_, body) => {
+            let mime = mime.unwrap_or_default();
+            let encoder = get_encoder(&mime)?;
+            encoder
+                .validate(&body)
+                .and_then(|_| encoder.encode(&body))
+                .map(|encoded| (utf8_content_type(Some(mime.clone()), charset), encoded.data))
+                .with_context(|| format!("can not encode the body as {}", mime))
+        }
+    }
+}
+
+// 非 utf8 的 charset 需要改为 utf-8，其他情况保持原来的 content-type
+fn utf8_content_type(mime: Option<String>, charset: Option<&str>) -> Option<String> {
+    match (mime, charset) {
+        (Some(mime), Some(charset)) if Encoding::for_label(charset.as_bytes()) != Some(UTF_8) => {
+            Some(format!("{}; charset=utf-8", mime))
+        }
+        _ => None,
+    }
+}
+
+fn is_binary_summary(text: &str) -> bool {
+    text.starts_with("00000000: ")
+        || (text.starts_with("<binary ") && text.contains(" bytes, sha256: "))
+}
+
+// 同名的 key 合并为数组
+pub(crate) fn decode_form(data: &[u8]) -> Value {
+    let mut map = Map::new();
+    for (k, v) in url::form_urlencoded::parse(data) {
+        let v = Value::String(v.into_owned());
+        match map.get_mut(k.as_ref()) {
+            Some(Value::Array(values)) => values.push(v),
+            Some(old) => *old = Value::Array(vec![old.take(), v]),
+            None => {
+                map.insert(k.into_owned(), v);
+            }
+        }
+    }
+    Value::Object(map)
+}
+
+fn binary_summary(data: &[u8]) -> String {
+    if data.len() > HEX_DUMP_LIMIT {
+        let digest = Sha256::digest(data);
+        let hash: String = digest.iter().map(|b| format!("{:02x}", b)).collect();
+        return format!("<binary {} bytes, sha256: {}>", data.len(), hash);
+    }
+    // 和 `xxd` 类似的格式：偏移量，16 个字节的 hex，可打印的字符
+    let mut output = String::new();
+    for (i, chunk) in data.chunks(16).enumerate() {
+        let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+        let ascii: String = chunk
+            .iter()
+            .map(|&b| {
+                if b.is_ascii_graphic() || b == b' ' {
+                    b as char
+                } else {
+                    '.'
+                }
+            })
+            .collect();
+        output.push_str(&format!(
+            "{:08x}: {:<47}  {}\n",
+            i * 16,
+            hex.join(" "),
+            ascii
+        ));
+    }
+    output
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Token<'a> {
+    Open(&'a str, String),
+    Close(&'a str, String),
+    // 自闭合的标签，注释，声明等不影响缩进的内容
+    Single(String),
+    Text(String),
+}
+
+// 把 xml / html 格式化为每行一个标签，只包含文本的标签保持在一行
+fn pretty_markup(text: &str, html: bool) -> String {
+    let tokens = tokenize_markup(text, html);
+    let mut output = String::new();
+    let mut depth = 0usize;
+    let mut i = 0;
+    while i < tokens.len() {
+        let indent = "  ".repeat(depth);
+        match (&tokens[i], tokens.get(i + 1), tokens.get(i + 2)) {
+            (Token::Open(name, open), Some(Token::Text(text)), Some(Token::Close(end, close)))
+                if name.eq_ignore_ascii_case(end) =>
+            {
+                output.push_str(&format!("{}{}{}{}\n", indent, open, text, close));
+                i += 3;
+                continue;
+            }
+            (Token::Open(name, open), Some(Token::Close(end, close)), _)
+                if name.eq_ignore_ascii_case(end) =>
+            {
+                output.push_str(&format!("{}{}{}\n", indent, open, close));
+                i += 2;
+                continue;
+            }
+            (Token::Open(_, open), _, _) => {
+                output.push_str(&format!("{}{}\n", indent, open));
+                depth += 1;
+            }
+            (Token::Close(_, close), _, _) => {
+                depth = depth.saturating_sub(1);
+                output.push_str(&format!("{}{}\n", "  ".repeat(depth), close));
+            }
+            (Token::Single(s) | Token::Text(s), _, _) => {
+                output.push_str(&format!("{}{}\n", indent, s))
+            }
+        }
+        i += 1;
+    }
+    output
+}
+
+fn tokenize_markup(text: &str, html: bool) -> Vec<Token<'_>> {
+    let mut tokens = vec![];
+    let mut rest = text;
+    while !rest.is_empty() {
+        if !rest.starts_with('<') {
+            let end = rest.find('<').unwrap_or(rest.len());
+            let text = normalize_space(&rest[..end]);
+            if !text.is_empty() {
+                tokens.push(Token::Text(text));
+            }
+            rest = &rest[end..];
+            continue;
+        }
+        let (end, raw) = if rest.starts_with("<!--") {
+            (rest.find("-->").map_or(rest.len(), |i| i + 3), true)
+        } else if rest.starts_with("<![CDATA[") {
+            (rest.find("]]>").map_or(rest.len(), |i| i + 3), true)
+        } else {
+            (tag_end(rest), false)
+        };
+        let tag = &rest[..end];
+        rest = &rest[end..];
+        if raw {
+            tokens.push(Token::Single(tag.trim().to_string()));
+            continue;
+        }
+        let normalized = normalize_space(tag);
+        let name_end = tag[1..]
+            .find(|c: char| c.is_whitespace() || c == '>' || c == '/')
+            .map_or(tag.len(), |i| i + 1);
+        if let Some(name) = tag.strip_prefix("</") {
+            let name = name.trim_end_matches('>').trim();
+            tokens.push(Token::Close(name, normalized));
+            continue;
+        }
+        let name = &tag[1..name_end];
+        let void = html && HTML_VOID_TAGS.iter().any(|t| t.eq_ignore_ascii_case(name));
+        if tag.starts_with("<?") || tag.starts_with("<!") || tag.ends_with("/>") || void {
+            tokens.push(Token::Single(normalized));
+            continue;
+        }
+        tokens.push(Token::Open(name, normalized));
+        // script 和 style 的内容原样保留
+        if html && (name.eq_ignore_ascii_case("script") || name.eq_ignore_ascii_case("style")) {
+            let close = format!("</{}", name);
+            let end = find_ignore_ascii_case(rest, &close).unwrap_or(rest.len());
+            let content = rest[..end].trim();
+            if !content.is_empty() {
+                tokens.push(Token::Text(content.to_string()));
+            }
+            rest = &rest[end..];
+        }
+    }
+    tokens
+}
+
+// 在原字符串上按 ascii 忽略大小写查找，返回的位置可以直接用于切分原字符串
+fn find_ignore_ascii_case(haystack: &str, needle: &str) -> Option<usize> {
+    haystack
+        .as_bytes()
+        .windows(needle.len())
+        .position(|w| w.eq_ignore_ascii_case(needle.as_bytes()))
+}
+
+// 找到标签的结束位置，忽略属性值中的 `>`
+fn tag_end(s: &str) -> usize {
+    let mut quote = None;
+    for (i, c) in s.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if q == c => quote = None,
+            (None, '>') => return i + 1,
+            _ => {}
+        }
+    }
+    s.len()
+}
+
+fn normalize_space(s: &str) -> String {
+    s.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn encode_decoded_should_keep_the_recorded_format() {
+        let body = json!({"a": ["1", "2"], "b": "x y"});
+        let form = "application/x-www-form-urlencoded";
+        let (content_type, data) = encode_decoded(Some(form), body.clone()).unwrap();
+        assert_eq!(content_type, None);
+        assert_eq!(data, b"a=1&a=2&b=x+y");
+        assert_eq!(decode_body(Some(form), &data).unwrap(), body);
+
+        for ct in [
+            "application/yaml",
+            "application/x-ndjson",
+            "application/msgpack",
+        ] {
+            let body = json!([{"a": 1}, {"b": "x"}]);
+            let (content_type, data) = encode_decoded(Some(ct), body.clone()).unwrap();
+            assert_eq!(content_type, None);
+            assert_eq!(decode_body(Some(ct), &data).unwrap(), body, "{}", ct);
+        }
+        // 非 utf8 的 charset 改为 utf-8
+        let (content_type, data) =
+            encode_decoded(Some("application/json; charset=gbk"), json!({"a": "你好"})).unwrap();
+        assert_eq!(
+            content_type.as_deref(),
+            Some("application/json; charset=utf-8")
+        );
+        assert_eq!(data, "{\"a\":\"你好\"}".as_bytes());
+        // 不能按原来的格式编码时拒绝回放
+        assert!(encode_decoded(Some(form), json!([1, 2])).is_err());
+    }
+
+    #[test]
+    fn decode_body_should_work() {
+        for ct in [
+            "application/json",
+            "application/msgpack",
+            "application/x-www-form-urlencoded",
+        ] {
+            assert_eq!(decode_body(Some(ct), b"").unwrap(), Value::Null, "{}", ct);
+        }
+        assert_eq!(decode_body(Some("text/plain"), b"").unwrap(), json!(""));
+        let xml = "<?xml version=\"1.0\"?>\n<todo id=\"1\">\n  <title>  hello\n world </title><tags><tag>a</tag><empty/></tags></todo>";
+        assert_eq!(
+            decode_body(Some("application/xml"), xml.as_bytes()).unwrap(),
+            json!("<?xml version=\"1.0\"?>\n<todo id=\"1\">\n  <title>hello world</title>\n  <tags>\n    <tag>a</tag>\n    <empty/>\n  </tags>\n</todo>\n")
+        );
+        let html = "<html><head><meta charset=\"utf-8\"><script>if (a > b) {}</script></head><body><br><p>hi</p></body></html>";
+        assert_eq!(
+            decode_body(Some("text/html"), html.as_bytes()).unwrap(),
+            json!("<html>\n  <head>\n    <meta charset=\"utf-8\">\n    <script>if (a > b) {}</script>\n  </head>\n  <body>\n    <br>\n    <p>hi</p>\n  </body>\n</html>\n")
+        );
+        assert_eq!(
+            decode_body(Some("application/x-ndjson"), b"{\"a\":1}\n\n{\"b\":2}\n").unwrap(),
+            json!([{"a": 1}, {"b": 2}])
+        );
+        assert_eq!(
+            decode_body(Some("application/x-www-form-urlencoded"), b"a=1&b=x+y&a=2").unwrap(),
+            json!({"a": ["1", "2"], "b": "x y"})
+        );
+        assert_eq!(
+            decode_body(Some("application/yaml"), b"a:\n- 1\n").unwrap(),
+            json!({"a": [1]})
+        );
+        let msgpack = rmp_serde::to_vec_named(&json!({"a": 1})).unwrap();
+        assert_eq!(
+            decode_body(Some("application/msgpack"), &msgpack).unwrap(),
+            json!({"a": 1})
+        );
+        assert_eq!(
+            decode_body(Some("image/png"), b"\x89PNG\r\n").unwrap(),
+            json!("00000000: 89 50 4e 47 0d 0a                                .PNG..\n")
+        );
+        // script 中的非 ascii 字符在转小写之后长度会变化
+        let html = "<script>var s = 'İİ';</SCRIPT><p>ok</p>";
+        assert_eq!(
+            decode_body(Some("text/html"), html.as_bytes()).unwrap(),
+            json!("<script>var s = 'İİ';</SCRIPT>\n<p>ok</p>\n")
+        );
+        // 按 charset 解码
+        assert_eq!(
+            decode_body(Some("text/html; charset=gbk"), b"<p>\xc4\xe3\xba\xc3</p>").unwrap(),
+            json!("<p>你好</p>\n")
+        );
+        assert_eq!(
+            decode_body(Some("text/plain;charset=\"ISO-8859-1\""), b"caf\xe9").unwrap(),
+            json!("café")
+        );
+        assert_eq!(
+            decode_body(Some("application/json; charset=utf-8"), b"{\"a\":1}").unwrap(),
+            json!({"a": 1})
+        );
+        let large = vec![0u8; HEX_DUMP_LIMIT + 1];
+        assert!(decode_body(None, &large)
+            .unwrap()
+            .as_str()
+            .unwrap()
+            .starts_with("<binary 513 bytes, sha256: "));
+    }
+}
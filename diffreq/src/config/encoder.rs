@@ -0,0 +1,404 @@
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::{Arc, OnceLock, RwLock},
+};
+
+use anyhow::{anyhow, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde_json::Value;
+
+use super::{multipart, query_pairs};
+use crate::util::xml_escape;
+
+// 根据 content-type 选择 body 的编码方式，内置的 encoder 在第一次使用时注册，
+// 也可以通过 `register_encoder` 注册新的 content-type 或者覆盖内置的实现
+
+/// the encoded request body
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncodedBody {
+    pub data: Vec<u8>,
+    /// override the content-type header, e.g. multipart with boundary
+    pub content_type: Option<String>,
+}
+
+impl From<Vec<u8>> for EncodedBody {
+    fn from(data: Vec<u8>) -> Self {
+        Self {
+            data,
+            content_type: None,
+        }
+    }
+}
+
+/// encode the profile body for a content type
+pub trait BodyEncoder: Send + Sync {
+    /// check the body shape when loading the config
+    fn validate(&self, body: &Value) -> Result<()>;
+    fn encode(&self, body: &Value) -> Result<EncodedBody>;
+}
+
+type Registry = RwLock<HashMap<String, Arc<dyn BodyEncoder>>>;
+
+fn registry() -> &'static Registry {
+    static REGISTRY: OnceLock<Registry> = OnceLock::new();
+    REGISTRY.get_or_init(|| {
+        let builtin: Vec<(&str, Arc<dyn BodyEncoder>)> = vec![
+            ("application/json", Arc::new(JsonEncoder)),
+            ("application/x-www-form-urlencoded", Arc::new(FormEncoder)),
+            ("multipart/form-data", Arc::new(MultipartEncoder)),
+            ("text/plain", Arc::new(TextEncoder { quoted: false })),
+            ("ad-bill-pb/base64", Arc::new(TextEncoder { quoted: true })),
+            ("application/xml", Arc::new(XmlEncoder)),
+            ("text/xml", Arc::new(XmlEncoder)),
+            ("application/yaml", Arc::new(YamlEncoder)),
+            ("application/x-yaml", Arc::new(YamlEncoder)),
+            ("text/yaml", Arc::new(YamlEncoder)),
+            ("application/x-ndjson", Arc::new(NdjsonEncoder)),
+            ("application/octet-stream", Arc::new(OctetStreamEncoder)),
+            ("application/msgpack", Arc::new(MsgpackEncoder)),
+            ("application/x-msgpack", Arc::new(MsgpackEncoder)),
+        ];
+        RwLock::new(
+            builtin
+                .into_iter()
+                .map(|(k, v)| (k.to_string(), v))
+                .collect(),
+        )
+    })
+}
+
+/// register an encoder for the content type, replace the existing one
+pub fn register_encoder(content_type: &str, encoder: impl BodyEncoder + 'static) {
+    registry()
+        .write()
+        .unwrap()
+        .insert(content_type.to_lowercase(), Arc::new(encoder));
+}
+
+/// get the encoder of the content type, `+json` / `+xml` / `+yaml` suffixes use the base encoder
+pub fn get_encoder(content_type: &str) -> Result<Arc<dyn BodyEncoder>> {
+    let content_type = content_type.trim().to_lowercase();
+    let encoders = registry().read().unwrap();
+    if let Some(encoder) = encoders.get(&content_type) {
+        return Ok(encoder.clone());
+    }
+    let suffix = content_type.rsplit_once('+').map(|(_, s)| s);
+    let base = match suffix {
+        Some("json") => "application/json",
+        Some("xml") => "application/xml",
+        Some("yaml") => "application/yaml",
+        _ => "",
+    };
+    encoders
+        .get(base)
+        .cloned()
+        .ok_or_else(|| anyhow!("Unsupported content type: {}", content_type))
+}
+
+fn expect_object(body: &Value) -> Result<()> {
+    if !body.is_object() {
+        return Err(anyhow!(
+            "Body must be an object: but got \n{}\n",
+            serde_yaml::to_string(body)?
+        ));
+    }
+    Ok(())
+}
+
+fn expect_string(body: &Value) -> Result<&str> {
+    body.as_str().ok_or_else(|| {
+        anyhow!(
+            "Body must be an string: but got \n{}\n",
+            serde_yaml::to_string(body).unwrap_or_default()
+        )
+    })
+}
+
+struct JsonEncoder;
+
+impl BodyEncoder for JsonEncoder {
+    // json 的 body 可以是任意的 json 值，包括数组，字符串和数字
+    fn validate(&self, _body: &Value) -> Result<()> {
+        Ok(())
+    }
+
+    fn encode(&self, body: &Value) -> Result<EncodedBody> {
+        Ok(serde_json::to_vec(body)?.into())
+    }
+}
+
+struct FormEncoder;
+
+impl BodyEncoder for FormEncoder {
+    fn validate(&self, body: &Value) -> Result<()> {
+        expect_object(body)
+    }
+
+    fn encode(&self, body: &Value) -> Result<EncodedBody> {
+        let pairs = query_pairs(body);
+        Ok(serde_urlencoded::to_string(pairs)?.into_bytes().into())
+    }
+}
+
+struct MultipartEncoder;
+
+impl BodyEncoder for MultipartEncoder {
+    fn validate(&self, body: &Value) -> Result<()> {
+        multipart::validate_multipart(body)
+    }
+
+    fn encode(&self, body: &Value) -> Result<EncodedBody> {
+        // boundary 每次请求都重新生成，并且需要带在 content-type 中
+        let (boundary, data) = multipart::encode_multipart(body)?;
+        Ok(EncodedBody {
+            data,
+            content_type: Some(format!("multipart/form-data; boundary={}", boundary)),
+        })
+    }
+}
+
+// 字符串原样发送，ad-bill-pb/base64 和之前的版本保持一致，按 json 序列化发送（带引号）
+struct TextEncoder {
+    quoted: bool,
+}
+
+impl BodyEncoder for TextEncoder {
+    fn validate(&self, body: &Value) -> Result<()> {
+        expect_string(body).map(|_| ())
+    }
+
+    fn encode(&self, body: &Value) -> Result<EncodedBody> {
+        let text = expect_string(body)?;
+        if self.quoted {
+            return Ok(body.to_string().into_bytes().into());
+        }
+        Ok(text.as_bytes().to_vec().into())
+    }
+}
+
+// xml 的 body 可以是字符串，也可以是只有一个根节点的 object：
+// `@name` 为属性，`#text` 为文本，数组表示同名的多个节点
+struct XmlEncoder;
+
+impl XmlEncoder {
+    fn root(body: &Value) -> Result<(&String, &Value)> {
+        match body.as_object() {
+            Some(map) if map.len() == 1 => Ok(map.iter().next().unwrap()),
+            _ => Err(anyhow!(
+                "Xml body must be a string or an object with a single root element"
+            )),
+        }
+    }
+}
+
+impl BodyEncoder for XmlEncoder {
+    fn validate(&self, body: &Value) -> Result<()> {
+        if !body.is_string() {
+            Self::root(body)?;
+        }
+        Ok(())
+    }
+
+    fn encode(&self, body: &Value) -> Result<EncodedBody> {
+        if let Value::String(s) = body {
+            return Ok(s.as_bytes().to_vec().into());
+        }
+        let (name, value) = Self::root(body)?;
+        let mut output = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>");
+        write_xml_element(&mut output, name, value);
+        Ok(output.into_bytes().into())
+    }
+}
+
+fn write_xml_element(output: &mut String, name: &str, value: &Value) {
+    match value {
+        Value::Array(items) => {
+            for item in items {
+                write_xml_element(output, name, item);
+            }
+        }
+        Value::Object(map) => {
+            output.push('<');
+            output.push_str(name);
+            for (k, v) in map {
+                if let Some(attr) = k.strip_prefix('@') {
+                    let v = v.as_str().map_or_else(|| v.to_string(), |s| s.to_string());
+                    output.push_str(&format!(" {}=\"{}\"", attr, xml_escape(&v)));
+                }
+            }
+            output.push('>');
+            for (k, v) in map {
+                match k.as_str() {
+                    k if k.starts_with('@') => {}
+                    "#text" => output.push_str(&xml_escape(&xml_text(v))),
+                    k => write_xml_element(output, k, v),
+                }
+            }
+            output.push_str(&format!("</{}>", name));
+        }
+        Value::Null => output.push_str(&format!("<{}/>", name)),
+        v => output.push_str(&format!("<{0}>{1}</{0}>", name, xml_escape(&xml_text(v)))),
+    }
+}
+
+fn xml_text(value: &Value) -> String {
+    value
+        .as_str()
+        .map_or_else(|| value.to_string(), |s| s.to_string())
+}
+
+struct YamlEncoder;
+
+impl BodyEncoder for YamlEncoder {
+    fn validate(&self, _body: &Value) -> Result<()> {
+        Ok(())
+    }
+
+    fn encode(&self, body: &Value) -> Result<EncodedBody> {
+        match body {
+            Value::String(s) => Ok(s.as_bytes().to_vec().into()),
+            v => Ok(serde_yaml::to_string(v)?.into_bytes().into()),
+        }
+    }
+}
+
+// ndjson 的 body 是数组，每个元素一行
+struct NdjsonEncoder;
+
+impl BodyEncoder for NdjsonEncoder {
+    fn validate(&self, body: &Value) -> Result<()> {
+        if !body.is_array() && !body.is_string() {
+            return Err(anyhow!(
+                "Ndjson body must be an array or a string: but got \n{}\n",
+                serde_yaml::to_string(body)?
+            ));
+        }
+        Ok(())
+    }
+
+    fn encode(&self, body: &Value) -> Result<EncodedBody> {
+        match body {
+            Value::Array(items) => {
+                let mut output = String::new();
+                for item in items {
+                    output.push_str(&serde_json::to_string(item)?);
+                    output.push('\n');
+                }
+                Ok(output.into_bytes().into())
+            }
+            v => Ok(expect_string(v)?.as_bytes().to_vec().into()),
+        }
+    }
+}
+
+// 二进制的 body：`@./path` 从文件中读取，其他的字符串按 base64 解码
+struct OctetStreamEncoder;
+
+impl BodyEncoder for OctetStreamEncoder {
+    fn validate(&self, body: &Value) -> Result<()> {
+        let s = expect_string(body)?;
+        match s.strip_prefix('@') {
+            Some(path) if !Path::new(path).is_file() => {
+                Err(anyhow!("Body file {} not found", path))
+            }
+            Some(_) => Ok(()),
+            None => STANDARD
+                .decode(s.trim())
+                .map(|_| ())
+                .with_context(|| "Binary body must be `@<file>` or a base64 string"),
+        }
+    }
+
+    fn encode(&self, body: &Value) -> Result<EncodedBody> {
+        let s = expect_string(body)?;
+        let data = match s.strip_prefix('@') {
+            Some(path) => {
+                std::fs::read(path).with_context(|| format!("read body file: {}", path))?
+            }
+            None => STANDARD.decode(s.trim())?,
+        };
+        Ok(data.into())
+    }
+}
+
+struct MsgpackEncoder;
+
+impl BodyEncoder for MsgpackEncoder {
+    fn validate(&self, _body: &Value) -> Result<()> {
+        Ok(())
+    }
+
+    fn encode(&self, body: &Value) -> Result<EncodedBody> {
+        Ok(rmp_serde::to_vec_named(body)?.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn encode(content_type: &str, body: Value) -> Vec<u8> {
+        let encoder = get_encoder(content_type).unwrap();
+        encoder.validate(&body).unwrap();
+        encoder.encode(&body).unwrap().data
+    }
+
+    #[test]
+    fn builtin_encoders_should_work() {
+        assert_eq!(encode("text/plain", json!("hello")), b"hello");
+        assert_eq!(encode("ad-bill-pb/base64", json!("aGk=")), b"\"aGk=\"");
+        assert_eq!(
+            encode("application/vnd.api+json", json!({"a": 1})),
+            b"{\"a\":1}"
+        );
+        // 子节点按照 yaml 中的顺序输出
+        let body: Value =
+            serde_yaml::from_str("todo:\n  '@id': 1\n  title: a<b\n  tag: [x, y]\n  done: null\n")
+                .unwrap();
+        assert_eq!(
+            encode("application/xml", body),
+            b"<?xml version=\"1.0\" encoding=\"UTF-8\"?><todo id=\"1\"><title>a&lt;b</title><tag>x</tag><tag>y</tag><done/></todo>"
+        );
+        assert_eq!(encode("application/yaml", json!({"a": [1]})), b"a:\n- 1\n");
+        assert_eq!(
+            encode("application/x-ndjson", json!([{"a": 1}, {"b": 2}])),
+            b"{\"a\":1}\n{\"b\":2}\n"
+        );
+        assert_eq!(
+            encode("application/octet-stream", json!("aGVsbG8=")),
+            b"hello"
+        );
+        let data = encode("application/msgpack", json!({"a": 1}));
+        let value: Value = rmp_serde::from_slice(&data).unwrap();
+        assert_eq!(value, json!({"a": 1}));
+
+        assert!(get_encoder("application/xml")
+            .unwrap()
+            .validate(&json!({"a": 1, "b": 2}))
+            .is_err());
+        assert!(get_encoder("image/png").is_err());
+    }
+
+    #[test]
+    fn octet_stream_file_should_be_relative_to_config_dir() {
+        use crate::{ConfigLoad, RequestConfig};
+
+        let (dir, path) = crate::util::temp_file("body.bin", [0xff, 0x00]);
+        let content = r#"
+upload:
+  method: PUT
+  url: https://example.com/files/a
+  headers:
+    content-type: application/octet-stream
+  body: "@body.bin"
+"#;
+        let config =
+            RequestConfig::from_yaml_in_dir(content, &Default::default(), dir.path()).unwrap();
+        let body = config.profiles["upload"].body.clone().unwrap();
+        assert_eq!(body, json!(format!("@{}", path.display())));
+        assert_eq!(encode("application/octet-stream", body), [0xff, 0x00]);
+        assert!(RequestConfig::from_yaml(content).is_err());
+    }
+}
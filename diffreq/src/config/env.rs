@@ -0,0 +1,119 @@
+use std::{collections::HashMap, path::Path};
+
+use anyhow::{anyhow, Context, Result};
+use serde_yaml::Value;
+
+// 配置文件中的 `${VAR}` / `${VAR:-default}` 替换，优先使用进程的环境变量，其次使用 .env 文件中的值
+// `$${VAR}` 表示不进行替换，输出为 `${VAR}`
+
+/// load key values from a `.env` file, return empty map if the file not exists
+pub fn load_dotenv(path: impl AsRef<Path>) -> Result<HashMap<String, String>> {
+    let path = path.as_ref();
+    if !path.is_file() {
+        return Ok(HashMap::new());
+    }
+    let content =
+        std::fs::read_to_string(path).with_context(|| format!("read {}", path.display()))?;
+    parse_dotenv(&content).with_context(|| format!("parse {}", path.display()))
+}
+
+fn parse_dotenv(content: &str) -> Result<HashMap<String, String>> {
+    let mut vars = HashMap::new();
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let (key, val) = line
+            .split_once('=')
+            .ok_or_else(|| anyhow!("Invalid line: {}", line))?;
+        let val = val.trim();
+        let val = match (val.chars().next(), val.chars().last()) {
+            (Some(q @ ('"' | '\'')), Some(e)) if val.len() > 1 && q == e => &val[1..val.len() - 1],
+            _ => val,
+        };
+        vars.insert(key.trim().to_string(), val.to_string());
+    }
+    Ok(vars)
+}
+
+/// replace `${VAR}` and `${VAR:-default}` in the text
+pub fn interpolate(text: &str, vars: &HashMap<String, String>) -> Result<String> {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(idx) = rest.find('$') {
+        output.push_str(&rest[..idx]);
+        rest = &rest[idx..];
+        if rest.starts_with("$${") {
+            output.push('$');
+            rest = &rest[2..];
+            let end = rest.find('}').map_or(rest.len(), |i| i + 1);
+            output.push_str(&rest[..end]);
+            rest = &rest[end..];
+            continue;
+        }
+        if !rest.starts_with("${") {
+            output.push('$');
+            rest = &rest[1..];
+            continue;
+        }
+        let end = rest
+            .find('}')
+            .ok_or_else(|| anyhow!("Unclosed variable in: {}", text))?;
+        let expr = &rest[2..end];
+        let (name, default) = match expr.split_once(":-") {
+            Some((name, default)) => (name, Some(default)),
+            None => (expr, None),
+        };
+        let val = std::env::var(name)
+            .ok()
+            .or_else(|| vars.get(name).cloned())
+            .or_else(|| default.map(|v| v.to_string()))
+            .ok_or_else(|| anyhow!("Environment variable {} is not set", name))?;
+        output.push_str(&val);
+        rest = &rest[end + 1..];
+    }
+    output.push_str(rest);
+    Ok(output)
+}
+
+/// replace the variables in all the string values of the yaml
+pub fn interpolate_yaml(value: &mut Value, vars: &HashMap<String, String>) -> Result<()> {
+    match value {
+        Value::String(s) if s.contains('$') => *s = interpolate(s, vars)?,
+        Value::Sequence(seq) => {
+            for v in seq {
+                interpolate_yaml(v, vars)?;
+            }
+        }
+        Value::Mapping(map) => {
+            for (_, v) in map.iter_mut() {
+                interpolate_yaml(v, vars)?;
+            }
+        }
+        Value::Tagged(tagged) => interpolate_yaml(&mut tagged.value, vars)?,
+        _ => {}
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interpolate_should_work() {
+        let vars = parse_dotenv("# comment\nexport TOKEN='abc'\nHOST = example.com\n").unwrap();
+        assert_eq!(
+            interpolate("https://${HOST}/a?t=${TOKEN}", &vars).unwrap(),
+            "https://example.com/a?t=abc"
+        );
+        assert_eq!(
+            interpolate("${XREQ_NOT_EXISTS:-default} $5 $${HOST}", &vars).unwrap(),
+            "default $5 ${HOST}"
+        );
+        assert!(interpolate("${XREQ_NOT_EXISTS}", &vars).is_err());
+        assert!(interpolate("${HOST", &vars).is_err());
+    }
+}
@@ -0,0 +1,282 @@
+use anyhow::{anyhow, Result};
+use clap::ValueEnum;
+use reqwest::{
+    header::{HeaderMap, CONTENT_TYPE},
+    Client,
+};
+use string_builder::Builder;
+use url::Url;
+
+use super::{
+    get_content_type,
+    multipart::{parse_parts, Part},
+    RequestProfile,
+};
+use crate::ExtraArgs;
+
+// xreq export 的输出格式
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum ExportFormat {
+    #[default]
+    Curl,
+    Httpie,
+    Http,
+}
+
+// 输出的 body：文本，或者由 curl 和 httpie 从文件中读取的二进制 body
+enum ExportBody<'a> {
+    Text(&'a str),
+    File(&'a str),
+}
+
+impl RequestProfile {
+    /// render the request (with extra args merged and auth applied) as a curl / httpie
+    /// command or raw http text, digest auth needs the challenge so it is not applied
+    pub fn export(&self, args: &ExtraArgs, format: ExportFormat) -> Result<String> {
+        let req = self.build_request(&Client::new(), args)?;
+        let (url, headers) = (req.url(), req.headers());
+        // multipart 的 body 是二进制的，curl 和 httpie 使用表单参数的写法
+        let form = match get_content_type(headers).as_deref() {
+            Some("multipart/form-data") => Some(parse_parts(&self.merge_args(args)?.2)?),
+            _ => None,
+        };
+        let data = req.body().and_then(|b| b.as_bytes()).unwrap_or_default();
+        if format == ExportFormat::Http {
+            return self.to_http(url, headers, data);
+        }
+        // `@file` 的二进制 body 由 curl 和 httpie 从文件中读取，其他的二进制 body 无法输出
+        let body = match (self.body_file(headers), std::str::from_utf8(data)) {
+            (Some(path), _) => ExportBody::File(path),
+            (None, Ok(text)) => ExportBody::Text(text),
+            (None, Err(_)) => return Err(binary_body_error(data)),
+        };
+        match format {
+            ExportFormat::Curl => Ok(self.to_curl(url, headers, &body, form)),
+            _ => Ok(self.to_httpie(url, headers, &body, form)),
+        }
+    }
+
+    // application/octet-stream 的 body 写成 `@./path` 时从文件中读取
+    fn body_file(&self, headers: &HeaderMap) -> Option<&str> {
+        if get_content_type(headers).as_deref() != Some("application/octet-stream") {
+            return None;
+        }
+        self.body.as_ref()?.as_str()?.strip_prefix('@')
+    }
+
+    fn to_curl(
+        &self,
+        url: &Url,
+        headers: &HeaderMap,
+        body: &ExportBody,
+        form: Option<Vec<Part>>,
+    ) -> String {
+        let mut parts = vec![format!(
+            "curl -X {} {}",
+            self.method,
+            shell_quote(url.as_str())
+        )];
+        for (k, v) in headers {
+            // multipart 的 content-type（带 boundary）由 curl 生成
+            if form.is_some() && k == CONTENT_TYPE {
+                continue;
+            }
+            let header = format!("{}: {}", k, String::from_utf8_lossy(v.as_bytes()));
+            parts.push(format!("-H {}", shell_quote(&header)));
+        }
+        match form {
+            Some(form) => {
+                for part in form {
+                    parts.push(format!("-F {}", shell_quote(&form_arg(&part, "="))));
+                }
+            }
+            None => match body {
+                ExportBody::Text("") => {}
+                ExportBody::Text(text) => parts.push(format!("--data-raw {}", shell_quote(text))),
+                ExportBody::File(path) => {
+                    let arg = format!("@{}", path);
+                    parts.push(format!("--data-binary {}", shell_quote(&arg)))
+                }
+            },
+        }
+        parts.join(" \\\n  ")
+    }
+
+    fn to_httpie(
+        &self,
+        url: &Url,
+        headers: &HeaderMap,
+        body: &ExportBody,
+        form: Option<Vec<Part>>,
+    ) -> String {
+        let multipart = if form.is_some() { " --multipart" } else { "" };
+        let mut parts = vec![format!(
+            "http{} {} {}",
+            multipart,
+            self.method,
+            shell_quote(url.as_str())
+        )];
+        for (k, v) in headers {
+            if form.is_some() && k == CONTENT_TYPE {
+                continue;
+            }
+            let header = format!("{}:{}", k, String::from_utf8_lossy(v.as_bytes()));
+            parts.push(shell_quote(&header));
+        }
+        match form {
+            Some(form) => {
+                for part in form {
+                    parts.push(shell_quote(&form_arg(&part, "")));
+                }
+            }
+            None => match body {
+                ExportBody::Text("") => {}
+                ExportBody::Text(text) => parts.push(format!("--raw {}", shell_quote(text))),
+                ExportBody::File(path) => parts.push(shell_quote(&format!("@{}", path))),
+            },
+        }
+        parts.join(" \\\n  ")
+    }
+
+    fn to_http(&self, url: &Url, headers: &HeaderMap, data: &[u8]) -> Result<String> {
+        let body = std::str::from_utf8(data).map_err(|_| binary_body_error(data))?;
+        let mut output_builder = Builder::default();
+        let path = match url.query() {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_string(),
+        };
+        output_builder.append(format!("{} {} HTTP/1.1\r\n", self.method, path));
+        if let Some(host) = url.host_str() {
+            match url.port() {
+                Some(port) => output_builder.append(format!("host: {}:{}\r\n", host, port)),
+                None => output_builder.append(format!("host: {}\r\n", host)),
+            }
+        }
+        for (k, v) in headers {
+            output_builder.append(format!("{}: {}\r\n", k, v.to_str()?));
+        }
+        if !data.is_empty() {
+            output_builder.append(format!("content-length: {}\r\n", data.len()));
+        }
+        output_builder.append("\r\n");
+        output_builder.append(body);
+        Ok(output_builder.string()?)
+    }
+}
+
+fn binary_body_error(data: &[u8]) -> anyhow::Error {
+    anyhow!(
+        "binary body ({} bytes) can not be exported as text, use an `@<file>` octet-stream body",
+        data.len()
+    )
+}
+
+// multipart 的 part 转换为 curl -F 的参数 `name=@path;type=...`，httpie 的写法为 `name@path;type=...`
+fn form_arg(part: &Part, file_sep: &str) -> String {
+    match part {
+        Part::Text { name, value, .. } => format!("{}={}", name, value),
+        Part::File {
+            name,
+            path,
+            content_type,
+            ..
+        } => {
+            let mut arg = format!("{}{}@{}", name, file_sep, path.display());
+            if let Some(content_type) = content_type {
+                arg.push_str(&format!(";type={}", content_type));
+            }
+            arg
+        }
+    }
+}
+
+// 对 shell 参数加单引号，单引号本身转义为 `'\''`
+fn shell_quote(s: &str) -> String {
+    let safe = !s.is_empty()
+        && s.chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./:=@%+,".contains(c));
+    if safe {
+        s.to_string()
+    } else {
+        format!("'{}'", s.replace('\'', "'\\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::parse_key_val;
+    use serde_json::json;
+
+    #[test]
+    fn export_should_work() {
+        let profile = RequestProfile::new(
+            reqwest::Method::POST,
+            Url::parse("https://example.com/todos").unwrap(),
+            Some(json!({"a": 1})),
+            HeaderMap::new(),
+            Some(json!({"title": "it's"})),
+        );
+        let args: ExtraArgs = vec![parse_key_val("b=hello world").unwrap()].into();
+        assert_eq!(
+            profile.export(&args, ExportFormat::Curl).unwrap(),
+            "curl -X POST 'https://example.com/todos?a=1&b=hello+world' \\\n  -H 'content-type: application/json' \\\n  --data-raw '{\"title\":\"it'\\''s\"}'"
+        );
+        assert_eq!(
+            profile.export(&args, ExportFormat::Http).unwrap(),
+            "POST /todos?a=1&b=hello+world HTTP/1.1\r\nhost: example.com\r\ncontent-type: application/json\r\ncontent-length: 16\r\n\r\n{\"title\":\"it's\"}"
+        );
+    }
+
+    #[test]
+    fn export_should_not_corrupt_binary_bodies() {
+        let (_dir, path) = crate::util::temp_file("body.bin", [0xff, 0x00, 0x01]);
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, "application/octet-stream".parse().unwrap());
+        let mut profile = RequestProfile::new(
+            reqwest::Method::PUT,
+            Url::parse("https://example.com/files/a").unwrap(),
+            None,
+            headers,
+            Some(json!(format!("@{}", path.display()))),
+        );
+        let args = ExtraArgs::default();
+        assert_eq!(
+            profile.export(&args, ExportFormat::Curl).unwrap(),
+            format!(
+                "curl -X PUT https://example.com/files/a \\\n  -H 'content-type: application/octet-stream' \\\n  --data-binary @{}",
+                path.display()
+            )
+        );
+        assert!(profile.export(&args, ExportFormat::Http).is_err());
+
+        profile.body = Some(json!("/wA="));
+        assert!(profile.export(&args, ExportFormat::Curl).is_err());
+        // content-length 是字节数
+        profile.body = Some(json!("5Lit"));
+        assert!(profile
+            .export(&args, ExportFormat::Http)
+            .unwrap()
+            .contains("content-length: 3\r\n"));
+    }
+
+    #[test]
+    fn export_should_apply_auth() {
+        let profile: RequestProfile = serde_yaml::from_str(
+            r#"
+url: https://example.com/todos
+auth:
+  type: basic
+  username: user
+  password: pass
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            profile
+                .export(&ExtraArgs::default(), ExportFormat::Curl)
+                .unwrap(),
+            "curl -X GET https://example.com/todos \\\n  -H 'content-type: application/json' \\\n  -H 'authorization: Basic dXNlcjpwYXNz' \\\n  --data-raw '{}'"
+        );
+    }
+}
@@ -0,0 +1,471 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Context, Result};
+use reqwest::{
+    header::{HeaderMap, SET_COOKIE},
+    Client,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{ConfigValidate, FilteredResponse, RequestProfile, ResponseProfile};
+use crate::{jsonpath::JsonPath, util::encode_uri_component, ExtraArgs};
+
+// 按顺序执行的多个请求，前面的响应中捕获的值作为变量，后面的 profile 中使用 `{{name}}` 引用
+// ```yaml
+// flows:
+//   login_todo:
+//     steps:
+//       - profile: login
+//         capture:
+//           token:
+//             body: $.data.token
+//           session:
+//             cookie: SESSION
+//       - profile: todo
+// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Flow {
+    pub steps: Vec<FlowStep>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FlowStep {
+    /// name of the profile in the config
+    pub profile: String,
+    /// variables captured from the response of this step
+    #[serde(skip_serializing_if = "BTreeMap::is_empty", default)]
+    pub capture: BTreeMap<String, Capture>,
+}
+
+// 只能指定其中的一个来源
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Capture {
+    /// json path into the body
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub body: Option<String>,
+    /// header name
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub header: Option<String>,
+    /// cookie name in the `set-cookie` headers
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub cookie: Option<String>,
+}
+
+/// the result of a flow step
+#[derive(Debug, Clone)]
+pub struct StepResult {
+    pub profile: String,
+    pub elapsed: Duration,
+    pub captured: BTreeMap<String, Value>,
+    pub response: FilteredResponse,
+}
+
+impl Flow {
+    /// run the steps in order, stop at the first failed step
+    pub async fn run(
+        &self,
+        profiles: &HashMap<String, RequestProfile>,
+        client: &Client,
+        args: &ExtraArgs,
+    ) -> Result<Vec<StepResult>> {
+        let mut vars = HashMap::new();
+        let mut results = vec![];
+        for (i, step) in self.steps.iter().enumerate() {
+            let result = step
+                .run(profiles, client, args, &mut vars)
+                .await
+                .with_context(|| format!("step {}: {}", i + 1, step.profile))?;
+            results.push(result);
+        }
+        Ok(results)
+    }
+}
+
+impl FlowStep {
+    async fn run(
+        &self,
+        profiles: &HashMap<String, RequestProfile>,
+        client: &Client,
+        args: &ExtraArgs,
+        vars: &mut HashMap<String, Value>,
+    ) -> Result<StepResult> {
+        let profile = profiles
+            .get(&self.profile)
+            .ok_or_else(|| anyhow!("Profile: {} not found", self.profile))?
+            .render(vars)?;
+        let start = Instant::now();
+        let res = profile.send(client, args).await?;
+        let headers = res.inner().headers().clone();
+        let response = res.filter(&ResponseProfile::default()).await?;
+        let elapsed = start.elapsed();
+
+        let mut captured = BTreeMap::new();
+        for (name, capture) in &self.capture {
+            let value = capture
+                .extract(&headers, &response.body)
+                .with_context(|| format!("capture: {}", name))?;
+            vars.insert(name.clone(), value.clone());
+            captured.insert(name.clone(), value);
+        }
+        Ok(StepResult {
+            profile: self.profile.clone(),
+            elapsed,
+            captured,
+            response,
+        })
+    }
+}
+
+impl Capture {
+    fn extract(&self, headers: &HeaderMap, body: &Value) -> Result<Value> {
+        match (&self.body, &self.header, &self.cookie) {
+            (Some(path), None, None) => path
+                .parse::<JsonPath>()?
+                .select(body)
+                .first()
+                .map(|v| (*v).clone())
+                .ok_or_else(|| anyhow!("{} not found in the body", path)),
+            (None, Some(name), None) => headers
+                .get(name.as_str())
+                .map(|v| Ok(Value::String(v.to_str()?.to_string())))
+                .unwrap_or_else(|| Err(anyhow!("header {} not found", name))),
+            (None, None, Some(name)) => headers
+                .get_all(SET_COOKIE)
+                .iter()
+                .filter_map(|v| v.to_str().ok())
+                .filter_map(|v| v.split(';').next()?.split_once('='))
+                .find(|(k, _)| k.trim() == name)
+                .map(|(_, v)| Value::String(v.trim().to_string()))
+                .ok_or_else(|| anyhow!("cookie {} not found", name)),
+            _ => Err(anyhow!(
+                "capture must have exactly one of `body`, `header` and `cookie`"
+            )),
+        }
+    }
+}
+
+impl ConfigValidate for Flow {
+    fn validate(&self) -> Result<()> {
+        if self.steps.is_empty() {
+            return Err(anyhow!("Flow must have at least one step"));
+        }
+        for step in &self.steps {
+            for (name, capture) in &step.capture {
+                let sources = [&capture.body, &capture.header, &capture.cookie];
+                if sources.iter().filter(|s| s.is_some()).count() != 1 {
+                    return Err(anyhow!(
+                        "capture {}: must have exactly one of `body`, `header` and `cookie`",
+                        name
+                    ));
+                }
+                if let Some(path) = &capture.body {
+                    path.parse::<JsonPath>()
+                        .with_context(|| format!("capture {}: {}", name, path))?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl RequestProfile {
+    /// replace `{{name}}` in the url, params, headers and body with the variables,
+    /// variables in the url path and query are percent-encoded
+    pub fn render(&self, vars: &HashMap<String, Value>) -> Result<RequestProfile> {
+        let mut value = serde_json::to_value(self)?;
+        if let Some(Value::String(url)) = value.get_mut("url") {
+            *url = render_url(url, vars)?;
+        }
+        render_value(&mut value, vars)?;
+        Ok(serde_json::from_value(value)?)
+    }
+}
+
+// profile 的 url 在加载时就需要解析，`{{name}}` 先替换为合法的占位符（host 中也可以使用），序列化时还原
+pub(super) mod template_url {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+    use url::Url;
+
+    const PREFIX: &str = "__var_";
+    const SUFFIX: &str = "__";
+
+    pub fn serialize<S: Serializer>(url: &Url, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&restore(url.as_str()))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Url, D::Error> {
+        let url = String::deserialize(deserializer)?;
+        Url::parse(&replace(&url)).map_err(D::Error::custom)
+    }
+
+    // `{{ name }}` => `__var_6e616d65__`，变量名使用 hex 编码，不受 host 转小写的影响
+    fn replace(url: &str) -> String {
+        let mut output = String::with_capacity(url.len());
+        let mut rest = url;
+        while let Some(start) = rest.find("{{") {
+            let end = match rest[start..].find("}}") {
+                Some(end) => start + end,
+                None => break,
+            };
+            let name = rest[start + 2..end].trim();
+            output.push_str(&rest[..start]);
+            output.push_str(PREFIX);
+            output.extend(name.bytes().map(|b| format!("{:02x}", b)));
+            output.push_str(SUFFIX);
+            rest = &rest[end + 2..];
+        }
+        output.push_str(rest);
+        output
+    }
+
+    fn restore(url: &str) -> String {
+        let mut output = String::with_capacity(url.len());
+        let mut rest = url;
+        while let Some(start) = rest.find(PREFIX) {
+            let hex_start = start + PREFIX.len();
+            let name = rest[hex_start..].find(SUFFIX).and_then(|len| {
+                let hex = &rest[hex_start..hex_start + len];
+                let bytes = (0..hex.len())
+                    .step_by(2)
+                    .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+                    .collect::<Option<Vec<_>>>()?;
+                Some((
+                    String::from_utf8(bytes).ok()?,
+                    hex_start + len + SUFFIX.len(),
+                ))
+            });
+            match name {
+                Some((name, end)) => {
+                    output.push_str(&rest[..start]);
+                    output.push_str("{{");
+                    output.push_str(&name);
+                    output.push_str("}}");
+                    rest = &rest[end..];
+                }
+                None => {
+                    output.push_str(&rest[..hex_start]);
+                    rest = &rest[hex_start..];
+                }
+            }
+        }
+        output.push_str(rest);
+        output
+    }
+}
+
+// host 部分原样替换，path 和 query 中的变量需要编码，比如 `a/b` 作为一个 path segment
+fn render_url(url: &str, vars: &HashMap<String, Value>) -> Result<String> {
+    let authority = url.find("://").map(|i| i + 3).unwrap_or(0);
+    let path = url[authority..]
+        .find(['/', '?', '#'])
+        .map(|i| authority + i)
+        .unwrap_or(url.len());
+    let host = render_string(&url[..path], vars, str::to_string)?;
+    let rest = render_string(&url[path..], vars, encode_uri_component)?;
+    Ok(host + &rest)
+}
+
+fn render_value(value: &mut Value, vars: &HashMap<String, Value>) -> Result<()> {
+    match value {
+        Value::String(s) if s.contains("{{") => *value = render_text(s, vars)?,
+        Value::Array(items) => {
+            for item in items {
+                render_value(item, vars)?;
+            }
+        }
+        Value::Object(map) => {
+            for (_, v) in map.iter_mut() {
+                render_value(v, vars)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn get_var<'a>(vars: &'a HashMap<String, Value>, name: &str) -> Result<&'a Value> {
+    vars.get(name.trim())
+        .ok_or_else(|| anyhow!("Variable {} is not captured", name.trim()))
+}
+
+// 整个字符串只有一个变量时保留变量原本的类型，比如 body 中的数字
+fn render_text(text: &str, vars: &HashMap<String, Value>) -> Result<Value> {
+    if let Some(name) = text
+        .strip_prefix("{{")
+        .and_then(|s| s.strip_suffix("}}"))
+        .filter(|s| !s.contains("{{"))
+    {
+        return Ok(get_var(vars, name)?.clone());
+    }
+    Ok(Value::String(render_string(text, vars, str::to_string)?))
+}
+
+fn render_string(
+    text: &str,
+    vars: &HashMap<String, Value>,
+    encode: impl Fn(&str) -> String,
+) -> Result<String> {
+    let mut output = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let end = rest[start..]
+            .find("}}")
+            .ok_or_else(|| anyhow!("Unclosed variable in: {}", text))?;
+        output.push_str(&rest[..start]);
+        match get_var(vars, &rest[start + 2..start + end])? {
+            Value::String(s) => output.push_str(&encode(s)),
+            v => output.push_str(&encode(&v.to_string())),
+        }
+        rest = &rest[start + end + 2..];
+    }
+    output.push_str(rest);
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn render_should_replace_variables() {
+        let profile: RequestProfile = serde_yaml::from_str(
+            r#"
+url: https://example.com/users/{{ user_id }}/todos
+headers:
+  authorization: Bearer {{token}}
+body:
+  user_id: "{{user_id}}"
+"#,
+        )
+        .unwrap();
+        let vars = [
+            ("token".to_string(), json!("abc")),
+            ("user_id".to_string(), json!(42)),
+        ]
+        .into_iter()
+        .collect();
+        let profile = profile.render(&vars).unwrap();
+        assert_eq!(profile.url.as_str(), "https://example.com/users/42/todos");
+        assert_eq!(profile.headers["authorization"], "Bearer abc");
+        assert_eq!(profile.body, Some(json!({"user_id": 42})));
+        assert!(profile.render(&HashMap::new()).is_ok());
+
+        let profile: RequestProfile =
+            serde_yaml::from_str("url: https://example.com/{{missing}}").unwrap();
+        assert!(profile.render(&vars).is_err());
+    }
+
+    #[test]
+    fn render_should_support_host_and_encode_path() {
+        let profile: RequestProfile =
+            serde_yaml::from_str("url: http://{{host}}/files/{{ name }}?q={{name}}").unwrap();
+        // 渲染前序列化出来的仍然是原始的写法
+        assert_eq!(
+            serde_json::to_value(&profile).unwrap()["url"],
+            "http://{{host}}/files/{{name}}?q={{name}}"
+        );
+        let vars = [
+            ("host".to_string(), json!("127.0.0.1:8080")),
+            ("name".to_string(), json!("a/b c")),
+        ]
+        .into_iter()
+        .collect();
+        let profile = profile.render(&vars).unwrap();
+        assert_eq!(
+            profile.url.as_str(),
+            "http://127.0.0.1:8080/files/a%2Fb%20c?q=a%2Fb%20c"
+        );
+    }
+
+    #[test]
+    fn capture_extract_should_work() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-request-id", "abc".parse().unwrap());
+        headers.append(SET_COOKIE, "theme=dark; Path=/".parse().unwrap());
+        headers.append(SET_COOKIE, "SESSION=s1; HttpOnly".parse().unwrap());
+        let body = json!({"data": {"token": "t1", "ids": [3, 4]}});
+
+        let capture = |yaml: &str| serde_yaml::from_str::<Capture>(yaml).unwrap();
+        let extract = |yaml: &str| capture(yaml).extract(&headers, &body);
+        assert_eq!(extract("body: $.data.token").unwrap(), json!("t1"));
+        assert_eq!(extract("body: data.ids[1]").unwrap(), json!(4));
+        assert_eq!(extract("header: x-request-id").unwrap(), json!("abc"));
+        assert_eq!(extract("cookie: SESSION").unwrap(), json!("s1"));
+        assert!(extract("body: $.data.missing").is_err());
+        assert!(extract("header: x-missing").is_err());
+        assert!(extract("cookie: missing").is_err());
+        assert!(extract("{body: $.data.token, header: x-request-id}").is_err());
+    }
+
+    #[tokio::test]
+    async fn flow_run_should_pass_captured_variables() {
+        let host = mockito::server_url().replace("http://", "");
+        let _login = mockito::mock("POST", "/flow/login")
+            .with_header("content-type", "application/json")
+            .with_header("set-cookie", "SESSION=s1; Path=/")
+            .with_header("x-host", &host)
+            .with_body(r#"{"data": {"token": "t1", "user_id": 7}}"#)
+            .create();
+        let _todos = mockito::mock("GET", "/flow/users/7/todos")
+            .match_header("authorization", "Bearer t1")
+            .match_header("cookie", "SESSION=s1")
+            .with_header("content-type", "application/json")
+            .with_body(r#"[{"id": 1}]"#)
+            .create();
+        let profiles: HashMap<String, RequestProfile> = serde_yaml::from_str(&format!(
+            r#"
+login:
+  method: POST
+  url: http://{host}/flow/login
+todos:
+  url: http://{{{{host}}}}/flow/users/{{{{user_id}}}}/todos
+  headers:
+    authorization: Bearer {{{{token}}}}
+    cookie: SESSION={{{{session}}}}
+"#
+        ))
+        .unwrap();
+        let mut flow: Flow = serde_yaml::from_str(
+            r#"
+steps:
+  - profile: login
+    capture:
+      token:
+        body: $.data.token
+      user_id:
+        body: $.data.user_id
+      session:
+        cookie: SESSION
+      host:
+        header: x-host
+  - profile: todos
+"#,
+        )
+        .unwrap();
+        let results = flow
+            .run(&profiles, &Client::new(), &ExtraArgs::default())
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].captured["token"], json!("t1"));
+        assert_eq!(results[0].captured["user_id"], json!(7));
+        assert_eq!(results[1].response.status, "HTTP/1.1 200 OK");
+        assert_eq!(results[1].response.body, json!([{"id": 1}]));
+
+        // capture 失败时 flow 停止
+        flow.steps[0].capture.insert(
+            "missing".into(),
+            serde_yaml::from_str("header: x-missing").unwrap(),
+        );
+        let err = flow
+            .run(&profiles, &Client::new(), &ExtraArgs::default())
+            .await
+            .unwrap_err();
+        assert!(format!("{:#}", err).contains("step 1: login: capture: missing"));
+    }
+}
@@ -0,0 +1,470 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+    str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE},
+    Method, Request,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use url::Url;
+
+use super::{
+    content_type_header,
+    decoder::decode_form,
+    get_content_type,
+    xdiff::{DiffConfig, DiffProfile, ResponseProfile},
+    xreq::RequestConfig,
+    RawResponse, RequestProfile,
+};
+use crate::util::{profile_name, unique_name, utc_datetime};
+
+// 导入时不保留的 header，http/2 的伪 header（`:authority` 等）也会被去掉
+// reqwest 没有开启解压缩，不能发送 accept-encoding
+const SKIP_HEADERS: [&str; 4] = ["host", "content-length", "connection", "accept-encoding"];
+
+// HAR 1.2 中用到的字段，导入时其他字段被忽略
+// http://www.softwareishard.com/blog/har-12-spec/
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Har {
+    pub log: HarLog,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HarLog {
+    pub version: String,
+    pub creator: HarCreator,
+    pub entries: Vec<HarEntry>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HarCreator {
+    pub name: String,
+    pub version: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct HarEntry {
+    pub started_date_time: String,
+    /// total time of the request in milliseconds
+    pub time: f64,
+    pub request: HarRequest,
+    pub response: HarResponse,
+    pub timings: HarTimings,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct HarRequest {
+    pub method: String,
+    pub url: String,
+    pub http_version: String,
+    pub headers: Vec<HarPair>,
+    pub query_string: Vec<HarPair>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub post_data: Option<HarPostData>,
+    pub headers_size: i64,
+    pub body_size: i64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct HarResponse {
+    pub status: u16,
+    pub status_text: String,
+    pub http_version: String,
+    pub headers: Vec<HarPair>,
+    pub content: HarContent,
+    #[serde(rename = "redirectURL")]
+    pub redirect_url: String,
+    pub headers_size: i64,
+    pub body_size: i64,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HarPair {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct HarPostData {
+    pub mime_type: String,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct HarContent {
+    pub size: i64,
+    pub mime_type: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    /// `base64` if the text is the base64 of a binary body
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encoding: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HarTimings {
+    pub send: f64,
+    pub wait: f64,
+    pub receive: f64,
+}
+
+impl Har {
+    /// a HAR log created by xreq
+    pub fn new(entries: Vec<HarEntry>) -> Self {
+        Self {
+            log: HarLog {
+                version: "1.2".into(),
+                creator: HarCreator {
+                    name: "xreq".into(),
+                    version: env!("CARGO_PKG_VERSION").into(),
+                },
+                entries,
+            },
+        }
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let content =
+            std::fs::read(path).with_context(|| format!("read har: {}", path.display()))?;
+        serde_json::from_slice(&content).with_context(|| format!("parse har: {}", path.display()))
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<()> {
+        let path = path.as_ref();
+        std::fs::write(path, serde_json::to_vec_pretty(self)?)
+            .with_context(|| format!("write har: {}", path.display()))
+    }
+
+    /// one profile per entry, named by the method and path, e.g. `get_api_todos_2`
+    pub fn to_request_config(&self) -> Result<RequestConfig> {
+        let mut names = HashSet::new();
+        let mut profiles = HashMap::new();
+        for (i, entry) in self.log.entries.iter().enumerate() {
+            let url = entry.url().with_context(|| format!("entry {}", i + 1))?;
+            let profile = entry
+                .to_profile(url)
+                .with_context(|| format!("entry {}: {}", i + 1, entry.request.url))?;
+            profiles.insert(unique_name(&mut names, &entry.name()), profile);
+        }
+        Ok(RequestConfig::new(profiles))
+    }
+
+    /// diff profiles of the entries, req2 is sent to the same path of the base url
+    pub fn to_diff_config(&self, base: &Url) -> Result<DiffConfig> {
+        let mut names = HashSet::new();
+        let mut profiles = HashMap::new();
+        for (i, entry) in self.log.entries.iter().enumerate() {
+            let context = || format!("entry {}: {}", i + 1, entry.request.url);
+            let url = entry.url().with_context(context)?;
+            let req1 = entry.to_profile(url.clone()).with_context(context)?;
+            let req2 = entry
+                .to_profile(rebase_url(&url, base))
+                .with_context(context)?;
+            let profile = DiffProfile::new(req1, req2, ResponseProfile::default());
+            profiles.insert(unique_name(&mut names, &entry.name()), profile);
+        }
+        Ok(DiffConfig::new(profiles))
+    }
+}
+
+impl HarEntry {
+    /// record the request and the raw response of an xreq run
+    pub fn new(req: &Request, res: &RawResponse, started: SystemTime, elapsed: Duration) -> Self {
+        let post_data = req
+            .body()
+            .and_then(|b| b.as_bytes())
+            .map(|data| HarPostData {
+                mime_type: get_content_type(req.headers()).unwrap_or_default(),
+                text: String::from_utf8_lossy(data).into_owned(),
+            });
+        let request = HarRequest {
+            method: req.method().to_string(),
+            url: req.url().to_string(),
+            http_version: "HTTP/1.1".into(),
+            headers: header_pairs(req.headers()),
+            query_string: req
+                .url()
+                .query_pairs()
+                .map(|(k, v)| HarPair {
+                    name: k.into_owned(),
+                    value: v.into_owned(),
+                })
+                .collect(),
+            body_size: post_data.as_ref().map_or(0, |p| p.text.len() as i64),
+            post_data,
+            headers_size: -1,
+        };
+
+        // status 形如 `HTTP/1.1 200 OK`
+        let mut status = res.status.splitn(3, ' ');
+        let http_version = status.next().unwrap_or_default().to_string();
+        let code = status
+            .next()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_default();
+        let status_text = status.next().unwrap_or_default().to_string();
+        // 文本的 body 原样记录，二进制的 body 记录为 base64
+        let (text, encoding) = match std::str::from_utf8(&res.data) {
+            Ok(text) if !text.contains('\0') => (text.to_string(), None),
+            _ => (STANDARD.encode(&res.data), Some("base64".to_string())),
+        };
+        let response = HarResponse {
+            status: code,
+            status_text,
+            http_version,
+            headers: header_pairs(&res.headers),
+            content: HarContent {
+                size: res.data.len() as i64,
+                mime_type: content_type_header(&res.headers)
+                    .unwrap_or_default()
+                    .to_string(),
+                text: Some(text),
+                encoding,
+            },
+            redirect_url: String::new(),
+            headers_size: -1,
+            body_size: res.data.len() as i64,
+        };
+
+        let time = elapsed.as_secs_f64() * 1000.0;
+        Self {
+            started_date_time: iso_datetime(started),
+            time,
+            request,
+            response,
+            timings: HarTimings {
+                send: 0.0,
+                wait: time,
+                receive: 0.0,
+            },
+        }
+    }
+
+    fn url(&self) -> Result<Url> {
+        Ok(Url::parse(&self.request.url)?)
+    }
+
+    fn name(&self) -> String {
+        let path = self.url().map(|u| u.path().to_string()).unwrap_or_default();
+        profile_name(&self.request.method, &path)
+    }
+
+    fn to_profile(&self, mut url: Url) -> Result<RequestProfile> {
+        let method = Method::from_str(&self.request.method.to_uppercase())?;
+        // query 的值保持为字符串，同名的参数合并为数组
+        let params = url
+            .query()
+            .map(|q| decode_form(q.as_bytes()))
+            .filter(|q| q.as_object().is_some_and(|q| !q.is_empty()));
+        url.set_query(None);
+
+        let mut headers = HeaderMap::new();
+        for pair in &self.request.headers {
+            let name = pair.name.to_lowercase();
+            if name.starts_with(':') || SKIP_HEADERS.contains(&name.as_str()) {
+                continue;
+            }
+            headers.append(
+                HeaderName::from_str(&name)?,
+                HeaderValue::from_str(&pair.value)?,
+            );
+        }
+
+        let body = match &self.request.post_data {
+            Some(data) if !data.text.is_empty() => {
+                if !data.mime_type.is_empty() && !headers.contains_key(CONTENT_TYPE) {
+                    headers.insert(CONTENT_TYPE, HeaderValue::from_str(&data.mime_type)?);
+                }
+                Some(post_body(data))
+            }
+            _ => None,
+        };
+        Ok(RequestProfile::new(method, url, params, headers, body))
+    }
+}
+
+// json 和 form 的 body 解析为对象，方便修改，form 中同名的参数合并为数组
+fn post_body(data: &HarPostData) -> Value {
+    let mime = data.mime_type.split(';').next().unwrap_or_default().trim();
+    if mime == "application/json" || mime.ends_with("+json") {
+        if let Ok(body) = serde_json::from_str(&data.text) {
+            return body;
+        }
+    }
+    if mime == "application/x-www-form-urlencoded" {
+        return decode_form(data.text.as_bytes());
+    }
+    Value::String(data.text.clone())
+}
+
+// 使用 base 的 scheme，host 和 port，base 的 path 作为前缀
+fn rebase_url(url: &Url, base: &Url) -> Url {
+    let mut rebased = base.clone();
+    let prefix = base.path().trim_end_matches('/');
+    rebased.set_path(&format!("{}{}", prefix, url.path()));
+    rebased.set_query(url.query());
+    rebased
+}
+
+fn header_pairs(headers: &HeaderMap) -> Vec<HarPair> {
+    headers
+        .iter()
+        .map(|(k, v)| HarPair {
+            name: k.to_string(),
+            value: String::from_utf8_lossy(v.as_bytes()).into_owned(),
+        })
+        .collect()
+}
+
+fn iso_datetime(time: SystemTime) -> String {
+    let since = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let (year, month, day, hour, minute, second) = utc_datetime(since.as_secs() as i64);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        hour,
+        minute,
+        second,
+        since.subsec_millis()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn har_import_should_work() {
+        let har: Har = serde_json::from_value(json!({
+            "log": {
+                "entries": [
+                    {
+                        "request": {
+                            "method": "GET",
+                            "url": "https://example.com/api/todos?page=1&tag=a&tag=b",
+                            "headers": [
+                                {"name": ":authority", "value": "example.com"},
+                                {"name": "Accept", "value": "application/json"}
+                            ]
+                        }
+                    },
+                    {
+                        "request": {
+                            "method": "POST",
+                            "url": "https://example.com/api/todos",
+                            "postData": {"mimeType": "application/json", "text": "{\"title\":\"a\"}"}
+                        }
+                    },
+                    {
+                        "request": {
+                            "method": "POST",
+                            "url": "https://example.com/login",
+                            "postData": {
+                                "mimeType": "application/x-www-form-urlencoded",
+                                "text": "user=a&role=x&role=y"
+                            }
+                        }
+                    },
+                    {"request": {"method": "GET", "url": "https://example.com/api/todos"}}
+                ]
+            }
+        }))
+        .unwrap();
+        let config = har.to_request_config().unwrap();
+        let mut names: Vec<_> = config.profiles.keys().cloned().collect();
+        names.sort();
+        assert_eq!(
+            names,
+            [
+                "get_api_todos",
+                "get_api_todos_2",
+                "post_api_todos",
+                "post_login"
+            ]
+        );
+        let profile = &config.profiles["get_api_todos"];
+        assert_eq!(
+            profile.params,
+            Some(json!({"page": "1", "tag": ["a", "b"]}))
+        );
+        assert_eq!(profile.headers.len(), 1);
+        let profile = &config.profiles["post_api_todos"];
+        assert_eq!(profile.body, Some(json!({"title": "a"})));
+        assert_eq!(profile.headers["content-type"], "application/json");
+        let profile = &config.profiles["post_login"];
+        assert_eq!(profile.body, Some(json!({"user": "a", "role": ["x", "y"]})));
+        // 重复的 form key 重新展开为多个同名的参数
+        let req = profile
+            .build_request(&reqwest::Client::new(), &Default::default())
+            .unwrap();
+        assert_eq!(
+            req.body().and_then(|b| b.as_bytes()),
+            Some(&b"user=a&role=x&role=y"[..])
+        );
+
+        let base = Url::parse("http://localhost:8080/v2/").unwrap();
+        let config = har.to_diff_config(&base).unwrap();
+        assert_eq!(
+            config.profiles["post_api_todos"]
+                .req2
+                .as_ref()
+                .unwrap()
+                .url
+                .as_str(),
+            "http://localhost:8080/v2/api/todos"
+        );
+    }
+
+    #[test]
+    fn har_entry_should_record_raw_response() {
+        let url = Url::parse("https://example.com/logo.png?a=1").unwrap();
+        let req = Request::new(Method::GET, url);
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("image/png"));
+        headers.append("set-cookie", HeaderValue::from_static("a=1"));
+        headers.append("set-cookie", HeaderValue::from_static("b=2"));
+        let res = RawResponse {
+            status: "HTTP/1.1 200 OK".into(),
+            headers,
+            data: b"\x89PNG\r\n\0".to_vec(),
+        };
+        let entry = HarEntry::new(&req, &res, UNIX_EPOCH, Duration::from_millis(5));
+        assert_eq!(entry.started_date_time, "1970-01-01T00:00:00.000Z");
+        assert_eq!(entry.request.query_string.len(), 1);
+        assert_eq!(entry.response.status, 200);
+        assert_eq!(entry.response.status_text, "OK");
+        let cookies: Vec<_> = entry
+            .response
+            .headers
+            .iter()
+            .filter(|h| h.name == "set-cookie")
+            .map(|h| h.value.as_str())
+            .collect();
+        assert_eq!(cookies, ["a=1", "b=2"]);
+        let content = &entry.response.content;
+        assert_eq!(content.size, 7);
+        assert_eq!(content.mime_type, "image/png");
+        assert_eq!(content.encoding.as_deref(), Some("base64"));
+        assert_eq!(content.text.as_deref(), Some("iVBORw0KAA=="));
+    }
+}
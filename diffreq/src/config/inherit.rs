@@ -0,0 +1,205 @@
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{anyhow, Result};
+use serde_yaml::Value;
+use url::Url;
+
+use super::ConfigValidate;
+
+// profile 的继承：顶层的 `defaults` 作用于所有的 profile，profile 中的 `extends: <name>` 继承另一个 profile
+// 合并的规则：mapping 递归合并，其他的值子 profile 覆盖父 profile，相对路径的 url 基于父 profile 的 url 拼接
+const DEFAULTS_KEY: &str = "defaults";
+const EXTENDS_KEY: &str = "extends";
+
+/// the `extends` of the profiles, broken ones are left unmerged by `resolve_profiles`
+/// and reported by `validate`
+#[derive(Debug, Default)]
+pub struct Extends {
+    names: HashSet<String>,
+    parents: HashMap<String, Value>,
+}
+
+/// resolve `defaults` and `extends` of the profiles in place, keys in `reserved` are not profiles
+pub fn resolve_profiles(value: &mut Value, reserved: &[&str]) -> Extends {
+    let map = match value.as_mapping_mut() {
+        Some(map) => map,
+        None => return Extends::default(),
+    };
+    let defaults = map.remove(DEFAULTS_KEY).unwrap_or(Value::Null);
+    let raw: HashMap<String, Value> = map
+        .iter()
+        .filter_map(|(k, v)| k.as_str().map(|k| (k.to_string(), v.clone())))
+        .filter(|(k, _)| !reserved.contains(&k.as_str()))
+        .collect();
+
+    let mut resolved = HashMap::new();
+    for name in raw.keys() {
+        let profile = resolve_one(name, &raw, &mut resolved, &mut vec![]);
+        map.insert(name.as_str().into(), merge(defaults.clone(), profile));
+    }
+    let names = raw.keys().cloned().collect();
+    let parents = raw
+        .into_iter()
+        .filter_map(|(name, profile)| Some((name, profile.get(EXTENDS_KEY)?.clone())))
+        .collect();
+    Extends { names, parents }
+}
+
+fn resolve_one(
+    name: &str,
+    raw: &HashMap<String, Value>,
+    resolved: &mut HashMap<String, Value>,
+    stack: &mut Vec<String>,
+) -> Value {
+    if let Some(profile) = resolved.get(name) {
+        return profile.clone();
+    }
+    let mut profile = raw.get(name).cloned().unwrap_or(Value::Null);
+    let parent = profile.as_mapping_mut().and_then(|m| m.remove(EXTENDS_KEY));
+    // 父 profile 不存在或者循环继承时不合并，由 validate 报错
+    let profile = match parent {
+        Some(Value::String(parent)) if raw.contains_key(&parent) && !stack.contains(&parent) => {
+            stack.push(name.to_string());
+            let base = resolve_one(&parent, raw, resolved, stack);
+            stack.pop();
+            merge(base, profile)
+        }
+        _ => profile,
+    };
+    resolved.insert(name.to_string(), profile.clone());
+    profile
+}
+
+impl ConfigValidate for Extends {
+    fn validate(&self) -> Result<()> {
+        let mut names: Vec<_> = self.parents.keys().collect();
+        names.sort();
+        for name in names {
+            let mut chain = vec![name.as_str()];
+            let mut current = name.as_str();
+            while let Some(parent) = self.parents.get(current) {
+                let parent = parent.as_str().ok_or_else(|| {
+                    anyhow!(
+                        "Profile: {} extends must be a profile name, but got {:?}",
+                        current,
+                        parent
+                    )
+                })?;
+                if chain.contains(&parent) {
+                    chain.push(parent);
+                    return Err(anyhow!("Profile extends cycle: {}", chain.join(" -> ")));
+                }
+                if !self.names.contains(parent) {
+                    return Err(anyhow!(
+                        "Profile: {} extends unknown profile: {}",
+                        current,
+                        parent
+                    ));
+                }
+                chain.push(parent);
+                current = parent;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn merge(base: Value, over: Value) -> Value {
+    match (base, over) {
+        (Value::Mapping(mut base), Value::Mapping(over)) => {
+            for (k, v) in over {
+                let merged = match base.remove(&k) {
+                    Some(Value::String(base_url)) if k.as_str() == Some("url") => {
+                        Value::String(join_url(&base_url, v))
+                    }
+                    Some(b) => merge(b, v),
+                    None => v,
+                };
+                base.insert(k, merged);
+            }
+            Value::Mapping(base)
+        }
+        (base, Value::Null) => base,
+        (_, over) => over,
+    }
+}
+
+// 子 profile 的 url 是相对路径时（比如 `/todos/1`），基于父 profile 的 url 拼接
+fn join_url(base: &str, url: Value) -> String {
+    let url = match url {
+        Value::String(url) => url,
+        _ => return base.to_string(),
+    };
+    if Url::parse(&url).is_ok() {
+        return url;
+    }
+    Url::parse(base)
+        .and_then(|b| b.join(&url))
+        .map(|u| u.to_string())
+        .unwrap_or(url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_profiles_should_work() {
+        let mut value: Value = serde_yaml::from_str(
+            r#"
+client:
+  timeout_ms: 100
+defaults:
+  url: https://example.com/api/
+  headers:
+    user-agent: xreq
+base:
+  params:
+    a: 1
+todo:
+  extends: base
+  url: todos/1
+  params:
+    b: 2
+"#,
+        )
+        .unwrap();
+        resolve_profiles(&mut value, &["client"])
+            .validate()
+            .unwrap();
+        let expected: Value = serde_yaml::from_str(
+            r#"
+client:
+  timeout_ms: 100
+base:
+  url: https://example.com/api/
+  headers:
+    user-agent: xreq
+  params:
+    a: 1
+todo:
+  url: https://example.com/api/todos/1
+  headers:
+    user-agent: xreq
+  params:
+    a: 1
+    b: 2
+"#,
+        )
+        .unwrap();
+        assert_eq!(value, expected);
+    }
+
+    #[test]
+    fn resolve_profiles_should_report_cycle_and_unknown_parent() {
+        let mut value: Value =
+            serde_yaml::from_str("a:\n  extends: b\nb:\n  extends: a\n").unwrap();
+        let extends = resolve_profiles(&mut value, &[]);
+        let err = extends.validate().unwrap_err();
+        assert_eq!(err.to_string(), "Profile extends cycle: a -> b -> a");
+
+        let mut value: Value = serde_yaml::from_str("a:\n  extends: c\n").unwrap();
+        let err = resolve_profiles(&mut value, &[]).validate().unwrap_err();
+        assert_eq!(err.to_string(), "Profile: a extends unknown profile: c");
+    }
+}
@@ -0,0 +1,352 @@
+use std::{
+    convert::Infallible,
+    future::Future,
+    net::SocketAddr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use anyhow::{anyhow, Context, Result};
+use hyper::{
+    body::Bytes,
+    header::{
+        HeaderName, ACCEPT_ENCODING, CONNECTION, CONTENT_LENGTH, HOST, PROXY_AUTHORIZATION, TE,
+        TRAILER, TRANSFER_ENCODING, UPGRADE,
+    },
+    service::{make_service_fn, service_fn},
+    Body, HeaderMap, Request, Response, Server, StatusCode,
+};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
+use url::Url;
+
+use super::{
+    client::ClientConfig,
+    is_default,
+    xdiff::{DiffResult, ResponseProfile},
+    ConfigValidate, FilteredResponse,
+};
+
+// 逐跳的 header 不转发
+const HOP_HEADERS: [HeaderName; 9] = [
+    CONNECTION,
+    HeaderName::from_static("keep-alive"),
+    CONTENT_LENGTH,
+    HOST,
+    PROXY_AUTHORIZATION,
+    TE,
+    TRAILER,
+    TRANSFER_ENCODING,
+    UPGRADE,
+];
+
+// candidate 没有配置超时时间时使用的默认值
+const CANDIDATE_TIMEOUT_MS: u64 = 30_000;
+
+// xdiff mirror 使用的配置，收到的请求同时转发给 primary 和 candidate，
+// 返回 primary 的响应，并输出两个响应经过 res 过滤之后的 diff
+// ```yaml
+// mirror:
+//   primary: http://127.0.0.1:8080
+//   candidate: http://127.0.0.1:9090
+//   candidate_timeout_ms: 5000
+//   res:
+//     skip_headers:
+//       - date
+// ```
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct MirrorConfig {
+    /// base url of the upstream whose responses are returned to the caller
+    pub primary: Url,
+    /// base url of the upstream to compare with
+    pub candidate: Url,
+    /// timeout of the candidate request, the diff is dropped after it
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub candidate_timeout_ms: Option<u64>,
+    #[serde(skip_serializing_if = "is_default", default)]
+    pub res: ResponseProfile,
+}
+
+// 一个 upstream 的响应，body 已经读取出来，解码失败只影响 diff，不影响返回原始的响应
+struct Upstream {
+    status: StatusCode,
+    headers: HeaderMap,
+    data: Bytes,
+    filtered: Result<FilteredResponse>,
+    elapsed: Duration,
+}
+
+impl ConfigValidate for MirrorConfig {
+    fn validate(&self) -> Result<()> {
+        for url in [&self.primary, &self.candidate] {
+            if !matches!(url.scheme(), "http" | "https") {
+                return Err(anyhow!("{} must be an http or https url", url));
+            }
+        }
+        self.res.validate()
+    }
+}
+
+impl MirrorConfig {
+    /// the client forwarding the requests, redirects and cookies are passed to the caller as is
+    pub fn client(config: &ClientConfig) -> Result<Client> {
+        ClientConfig {
+            max_redirects: Some(0),
+            cookie_jar: None,
+            ..config.clone()
+        }
+        .build()
+    }
+
+    /// bind the address, the returned future runs the proxy until error
+    pub fn bind(
+        self,
+        client: &ClientConfig,
+        addr: SocketAddr,
+    ) -> Result<(SocketAddr, impl Future<Output = Result<()>>)> {
+        let client = Self::client(client)?;
+        let config = Arc::new(self);
+        let make_svc = make_service_fn(move |_| {
+            let (config, client) = (config.clone(), client.clone());
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req| {
+                    let (config, client) = (config.clone(), client.clone());
+                    async move { Ok::<_, Infallible>(config.handle(&client, req).await) }
+                }))
+            }
+        });
+        let server = Server::try_bind(&addr)
+            .with_context(|| format!("bind mirror proxy: {}", addr))?
+            .serve(make_svc);
+        let addr = server.local_addr();
+        Ok((addr, async move { Ok(server.await?) }))
+    }
+
+    async fn handle(self: &Arc<Self>, client: &Client, req: Request<Body>) -> Response<Body> {
+        let label = format!("{} {}", req.method(), req.uri());
+        match self.forward(client, req).await {
+            Ok((res, diff)) => {
+                // diff 在后台完成之后再输出，不阻塞 primary 响应的返回
+                tokio::spawn(async move {
+                    let log = match diff.await.map_err(anyhow::Error::from) {
+                        Ok(Ok(result)) if result.is_same() => format!("{} same\n", label),
+                        Ok(Ok(result)) => format!(
+                            "{} different\n{}",
+                            label,
+                            result.text().unwrap_or_else(|e| format!("{:#}\n", e))
+                        ),
+                        Ok(Err(e)) | Err(e) => format!("{} candidate error: {:#}\n", label, e),
+                    };
+                    // 并发的请求一次输出完整的日志，避免交错
+                    print!("{}", log);
+                });
+                res
+            }
+            Err(e) => {
+                println!("{} primary error: {:#}", label, e);
+                let mut res = Response::new(Body::from(format!("{:#}\n", e)));
+                *res.status_mut() = StatusCode::BAD_GATEWAY;
+                res
+            }
+        }
+    }
+
+    /// send the request to both upstreams, return the primary response as soon as it arrives
+    /// and a task diffing it with the candidate response
+    async fn forward(
+        self: &Arc<Self>,
+        client: &Client,
+        req: Request<Body>,
+    ) -> Result<(Response<Body>, JoinHandle<Result<DiffResult>>)> {
+        let (parts, body) = req.into_parts();
+        let body = hyper::body::to_bytes(body).await?;
+        let path = parts
+            .uri
+            .path_and_query()
+            .map(|p| p.as_str())
+            .unwrap_or("/");
+        let mut headers = parts.headers.clone();
+        for name in &HOP_HEADERS {
+            headers.remove(name);
+        }
+        // 压缩的响应无法解码做 diff，不转发 accept-encoding
+        headers.remove(ACCEPT_ENCODING);
+        let request = |base: &Url| {
+            let url = format!("{}{}", base.as_str().trim_end_matches('/'), path);
+            client
+                .request(parts.method.clone(), url)
+                .headers(headers.clone())
+                .body(body.clone())
+        };
+        // candidate 在单独的 task 中请求，失败或者超时都不影响返回 primary 的响应
+        let mut candidate = tokio::spawn({
+            let (config, req) = (self.clone(), request(&self.candidate));
+            async move { config.send(req).await }
+        });
+        let primary = match self.send(request(&self.primary)).await {
+            Ok(primary) => primary,
+            Err(e) => {
+                candidate.abort();
+                return Err(e.context("primary"));
+            }
+        };
+
+        let (mode, filtered, elapsed1) = (self.res.mode, primary.filtered, primary.elapsed);
+        let timeout =
+            Duration::from_millis(self.candidate_timeout_ms.unwrap_or(CANDIDATE_TIMEOUT_MS));
+        let diff = tokio::spawn(async move {
+            let candidate = match tokio::time::timeout(timeout, &mut candidate).await {
+                Ok(joined) => joined?.context("candidate")?,
+                Err(_) => {
+                    candidate.abort();
+                    return Err(anyhow!("candidate: timed out after {:?}", timeout));
+                }
+            };
+            Ok(DiffResult {
+                mode,
+                res1: filtered.context("decode primary response")?,
+                res2: candidate.filtered.context("decode candidate response")?,
+                elapsed1,
+                elapsed2: candidate.elapsed,
+                snapshot: false,
+            })
+        });
+
+        let mut res = Response::new(Body::from(primary.data));
+        *res.status_mut() = primary.status;
+        *res.headers_mut() = primary.headers;
+        for name in &HOP_HEADERS {
+            res.headers_mut().remove(name);
+        }
+        Ok((res, diff))
+    }
+
+    async fn send(&self, req: reqwest::RequestBuilder) -> Result<Upstream> {
+        let start = Instant::now();
+        let res = req.send().await?;
+        let status = res.status();
+        let version = res.version();
+        let headers = res.headers().clone();
+        let data = res.bytes().await?;
+        let elapsed = start.elapsed();
+        let filtered = FilteredResponse::from_parts(
+            format!("{:?} {}", version, status),
+            &headers,
+            &data,
+            &self.res,
+        );
+        Ok(Upstream {
+            status,
+            headers,
+            data,
+            filtered,
+            elapsed,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ConfigLoad, MockConfig};
+
+    fn upstream(id: u32) -> SocketAddr {
+        // 只有 candidate 的 /slow 响应很慢
+        let yaml = format!(
+            "stubs:\n  - path: /todos/*\n    headers:\n      x-upstream: \"{}\"\n    body:\n      id: 1\n      title: todo {}\n  - path: /slow\n    delay_ms: {}\n    body: slow\n{}",
+            id, id, (id - 1) * 2000, EXTRA_STUBS
+        );
+        let (addr, server) = MockConfig::from_yaml(&yaml)
+            .unwrap()
+            .bind(([127, 0, 0, 1], 0).into())
+            .unwrap();
+        tokio::spawn(server);
+        addr
+    }
+
+    // 无法解码的 body 和重定向都原样返回
+    const EXTRA_STUBS: &str = r#"  - path: /broken
+    headers:
+      content-type: application/json
+    body: "{not json"
+  - path: /old
+    status: 302
+    headers:
+      location: /todos/1
+"#;
+
+    #[tokio::test]
+    async fn mirror_should_pass_raw_primary_response() {
+        let (primary, candidate) = (upstream(1), upstream(2));
+        let config: MirrorConfig = serde_yaml::from_str(&format!(
+            "primary: http://{}\ncandidate: http://{}\n",
+            primary, candidate
+        ))
+        .unwrap();
+        let config = Arc::new(config);
+        let client = MirrorConfig::client(&Default::default()).unwrap();
+
+        let req = Request::get("/broken").body(Body::empty()).unwrap();
+        let (res, diff) = config.forward(&client, req).await.unwrap();
+        let result = diff.await.unwrap();
+        assert_eq!(res.status(), 200);
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        assert_eq!(body, "{not json");
+        assert!(format!("{:#}", result.unwrap_err()).contains("decode primary response"));
+
+        let req = Request::get("/old").body(Body::empty()).unwrap();
+        let (res, diff) = config.forward(&client, req).await.unwrap();
+        let result = diff.await.unwrap();
+        assert_eq!(res.status(), 302);
+        assert_eq!(res.headers()["location"], "/todos/1");
+        assert!(result.unwrap().is_same());
+    }
+
+    #[tokio::test]
+    async fn mirror_should_not_wait_for_candidate() {
+        let (primary, candidate) = (upstream(1), upstream(2));
+        let config: MirrorConfig = serde_yaml::from_str(&format!(
+            "primary: http://{}\ncandidate: http://{}\ncandidate_timeout_ms: 200\n",
+            primary, candidate
+        ))
+        .unwrap();
+        let config = Arc::new(config);
+        let client = MirrorConfig::client(&Default::default()).unwrap();
+
+        let start = Instant::now();
+        let req = Request::get("/slow").body(Body::empty()).unwrap();
+        let (res, diff) = config.forward(&client, req).await.unwrap();
+        assert_eq!(res.status(), 200);
+        assert!(start.elapsed() < Duration::from_millis(200));
+        let err = diff.await.unwrap().unwrap_err();
+        assert_eq!(err.to_string(), "candidate: timed out after 200ms");
+        assert!(start.elapsed() < Duration::from_millis(2000));
+    }
+
+    #[tokio::test]
+    async fn mirror_should_return_primary_and_diff_candidate() {
+        let (primary, candidate) = (upstream(1), upstream(2));
+        let config: MirrorConfig = serde_yaml::from_str(&format!(
+            "primary: http://{}\ncandidate: http://{}\nres:\n  skip_headers:\n    - date\n    - x-upstream\n",
+            primary, candidate
+        ))
+        .unwrap();
+        let config = Arc::new(config);
+        config.validate().unwrap();
+
+        let client = MirrorConfig::client(&Default::default()).unwrap();
+        let req = Request::get("/todos/1?a=1").body(Body::empty()).unwrap();
+        let (res, diff) = config.forward(&client, req).await.unwrap();
+        let result = diff.await.unwrap();
+        assert_eq!(res.headers()["x-upstream"], "1");
+        let body = hyper::body::to_bytes(res.into_body()).await.unwrap();
+        assert_eq!(body, r#"{"id":1,"title":"todo 1"}"#);
+
+        let result = result.unwrap();
+        assert!(!result.is_same());
+        let changes = result.changes().unwrap();
+        assert!(changes.headers.is_empty());
+        assert_eq!(changes.body.len(), 1);
+    }
+}
@@ -0,0 +1,324 @@
+use std::{
+    collections::BTreeMap, convert::Infallible, future::Future, net::SocketAddr, path::Path,
+    str::FromStr, sync::Arc, time::Duration,
+};
+
+use anyhow::{anyhow, Context, Result};
+use hyper::{
+    header::{CONTENT_LENGTH, CONTENT_TYPE, TRANSFER_ENCODING},
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{decoder, snapshot, ConfigLoad, ConfigValidate, ResponseProfile};
+use crate::util::glob_match;
+
+// xreq serve 使用的 mock server 配置，按顺序匹配 stub，第一个匹配的 stub 返回响应
+// ```yaml
+// stubs:
+//   - method: GET
+//     path: /todos/*
+//     query:
+//       page: "1"
+//     status: 200
+//     headers:
+//       x-mock: "true"
+//     body:
+//       id: 1
+//     delay_ms: 100
+//   - path: /users/1
+//     snapshot: ./snapshots/user.yml
+// ```
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct MockConfig {
+    #[serde(default)]
+    pub stubs: Vec<Stub>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Stub {
+    /// match any method if not set
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub method: Option<String>,
+    /// glob pattern of the path, e.g. `/todos/*`
+    pub path: String,
+    /// glob patterns of the query values, other query params are ignored
+    #[serde(skip_serializing_if = "BTreeMap::is_empty", default)]
+    pub query: BTreeMap<String, String>,
+    /// default to 200, or the status of the snapshot
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub status: Option<u16>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty", default)]
+    pub headers: BTreeMap<String, String>,
+    /// string body is sent as is, other values are sent as json
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub body: Option<Value>,
+    /// response recorded by `xdiff run --update`, the fields above override it
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub snapshot: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub delay_ms: Option<u64>,
+}
+
+impl ConfigLoad for MockConfig {
+    const RESERVED_KEYS: &'static [&'static str] = &["stubs"];
+
+    // 快照的相对路径相对于配置文件所在的目录
+    fn resolve_paths(&mut self, dir: &Path) {
+        for stub in &mut self.stubs {
+            if let Some(path) = &mut stub.snapshot {
+                *path = dir.join(&*path).to_string_lossy().into_owned();
+            }
+        }
+    }
+}
+
+impl ConfigValidate for MockConfig {
+    fn validate(&self) -> Result<()> {
+        for (i, stub) in self.stubs.iter().enumerate() {
+            stub.validate()
+                .with_context(|| format!("stub {}: {}", i + 1, stub.path))?;
+        }
+        Ok(())
+    }
+}
+
+impl ConfigValidate for Stub {
+    fn validate(&self) -> Result<()> {
+        if !self.path.starts_with('/') {
+            return Err(anyhow!("path must start with `/`"));
+        }
+        if let Some(method) = &self.method {
+            Method::from_str(&method.to_uppercase())
+                .with_context(|| format!("invalid method: {}", method))?;
+        }
+        if let Some(status) = self.status {
+            StatusCode::from_u16(status).with_context(|| format!("invalid status: {}", status))?;
+        }
+        if let Some(path) = &self.snapshot {
+            if !Path::new(path).is_file() {
+                return Err(anyhow!("snapshot {} not found", path));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl MockConfig {
+    /// the first stub matching the request
+    pub fn find(&self, method: &Method, path: &str, query: Option<&str>) -> Option<&Stub> {
+        let query: Vec<(String, String)> = query
+            .map(|q| {
+                url::form_urlencoded::parse(q.as_bytes())
+                    .into_owned()
+                    .collect()
+            })
+            .unwrap_or_default();
+        self.stubs.iter().find(|s| s.matches(method, path, &query))
+    }
+
+    /// bind the address, the returned future runs the server until error
+    pub fn bind(self, addr: SocketAddr) -> Result<(SocketAddr, impl Future<Output = Result<()>>)> {
+        let config = Arc::new(self);
+        let make_svc = make_service_fn(move |_| {
+            let config = config.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |req| {
+                    let config = config.clone();
+                    async move { Ok::<_, Infallible>(config.handle(req).await) }
+                }))
+            }
+        });
+        let server = Server::try_bind(&addr)
+            .with_context(|| format!("bind mock server: {}", addr))?
+            .serve(make_svc);
+        let addr = server.local_addr();
+        Ok((addr, async move { Ok(server.await?) }))
+    }
+
+    async fn handle(&self, req: Request<Body>) -> Response<Body> {
+        let (method, uri) = (req.method(), req.uri());
+        let res = match self.find(method, uri.path(), uri.query()) {
+            Some(stub) => stub.response().await.unwrap_or_else(|e| {
+                text_response(StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}\n", e))
+            }),
+            None => text_response(
+                StatusCode::NOT_FOUND,
+                format!("no stub matched: {} {}\n", method, uri),
+            ),
+        };
+        println!("{} {} -> {}", method, uri, res.status().as_u16());
+        res
+    }
+}
+
+impl Stub {
+    fn matches(&self, method: &Method, path: &str, query: &[(String, String)]) -> bool {
+        self.method
+            .as_ref()
+            .is_none_or(|m| m.eq_ignore_ascii_case(method.as_str()))
+            && glob_match(&self.path, path)
+            && self
+                .query
+                .iter()
+                .all(|(k, p)| query.iter().any(|(qk, qv)| qk == k && glob_match(p, qv)))
+    }
+
+    async fn response(&self) -> Result<Response<Body>> {
+        let (mut status, mut headers, mut data) = (200, BTreeMap::new(), None);
+        if let Some(path) = &self.snapshot {
+            let saved = snapshot::load(path, &ResponseProfile::default())?;
+            // 快照中的 status 形如 `HTTP/1.1 200 OK`
+            status = saved
+                .status
+                .split_whitespace()
+                .nth(1)
+                .and_then(|s| s.parse().ok())
+                .ok_or_else(|| anyhow!("invalid status in snapshot: {}", saved.status))?;
+            headers = saved.headers;
+            // 快照中的 body 是解码之后的，需要重新编码
+            let content_type = headers.get(CONTENT_TYPE.as_str()).map(String::as_str);
+            let (content_type, body) = decoder::encode_decoded(content_type, saved.body)
+                .with_context(|| format!("replay snapshot: {}", path))?;
+            if let Some(content_type) = content_type {
+                headers.insert(CONTENT_TYPE.to_string(), content_type);
+            }
+            data = Some(body);
+        }
+        status = self.status.unwrap_or(status);
+        headers.extend(self.headers.clone());
+        if let Some(body) = &self.body {
+            data = Some(match body {
+                Value::Null => vec![],
+                Value::String(text) => text.clone().into_bytes(),
+                value => {
+                    headers
+                        .entry(CONTENT_TYPE.to_string())
+                        .or_insert_with(|| "application/json".to_string());
+                    serde_json::to_vec(value)?
+                }
+            });
+        }
+        if let Some(delay) = self.delay_ms {
+            tokio::time::sleep(Duration::from_millis(delay)).await;
+        }
+
+        let data = data.unwrap_or_default();
+        let mut builder = Response::builder().status(status);
+        for (k, v) in &headers {
+            // 长度由 hyper 根据实际的 body 计算
+            if k.eq_ignore_ascii_case(CONTENT_LENGTH.as_str())
+                || k.eq_ignore_ascii_case(TRANSFER_ENCODING.as_str())
+            {
+                continue;
+            }
+            builder = builder.header(k.as_str(), v.as_str());
+        }
+        Ok(builder.body(Body::from(data))?)
+    }
+}
+
+fn text_response(status: StatusCode, text: String) -> Response<Body> {
+    let mut res = Response::new(Body::from(text));
+    *res.status_mut() = status;
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn mock_server_should_answer_stubs() {
+        let config = MockConfig::from_yaml(
+            r#"
+stubs:
+  - method: get
+    path: /todos/*
+    query:
+      page: "1*"
+    headers:
+      x-mock: "true"
+    body:
+      id: 1
+  - path: /health
+    status: 204
+"#,
+        )
+        .unwrap();
+        let (addr, server) = config.bind(([127, 0, 0, 1], 0).into()).unwrap();
+        tokio::spawn(server);
+
+        let url = format!("http://{}", addr);
+        let res = reqwest::get(format!("{}/todos/1?page=10", url))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), 200);
+        assert_eq!(res.headers()["x-mock"], "true");
+        assert_eq!(res.headers()["content-type"], "application/json");
+        assert_eq!(res.text().await.unwrap(), r#"{"id":1}"#);
+
+        let res = reqwest::get(format!("{}/todos/1?page=2", url))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), 404);
+        let res = reqwest::get(format!("{}/health", url)).await.unwrap();
+        assert_eq!(res.status(), 204);
+    }
+
+    #[tokio::test]
+    async fn mock_server_should_replay_snapshots() {
+        let dir = tempfile::tempdir().unwrap();
+        let snapshots = [
+            (
+                "todo.yml",
+                "status: HTTP/1.1 201 Created\nheaders:\n  content-type: application/x-yaml\nbody:\n  id: 1\n  title: hello\n",
+            ),
+            (
+                "page.yml",
+                "status: HTTP/1.1 200 OK\nheaders:\n  content-type: text/html; charset=gbk\nbody: \"<p>你好</p>\\n\"\n",
+            ),
+            (
+                "logo.yml",
+                "status: HTTP/1.1 200 OK\nheaders:\n  content-type: image/png\nbody: \"00000000: 89 50 4e 47\"\n",
+            ),
+        ];
+        std::fs::create_dir(dir.path().join("snapshots")).unwrap();
+        for (name, content) in snapshots {
+            std::fs::write(dir.path().join("snapshots").join(name), content).unwrap();
+        }
+        let config = MockConfig::from_yaml_in_dir(
+            r#"
+stubs:
+  - path: /todo
+    snapshot: snapshots/todo.yml
+  - path: /page
+    snapshot: snapshots/page.yml
+  - path: /logo
+    snapshot: snapshots/logo.yml
+"#,
+            &Default::default(),
+            dir.path(),
+        )
+        .unwrap();
+        let (addr, server) = config.bind(([127, 0, 0, 1], 0).into()).unwrap();
+        tokio::spawn(server);
+
+        let url = format!("http://{}", addr);
+        // yaml 按照记录的格式重新编码
+        let res = reqwest::get(format!("{}/todo", url)).await.unwrap();
+        assert_eq!(res.status(), 201);
+        assert_eq!(res.headers()["content-type"], "application/x-yaml");
+        assert_eq!(res.text().await.unwrap(), "id: 1\ntitle: hello\n");
+
+        let res = reqwest::get(format!("{}/page", url)).await.unwrap();
+        assert_eq!(res.headers()["content-type"], "text/html; charset=utf-8");
+        assert_eq!(res.text().await.unwrap(), "<p>你好</p>\n");
+
+        // 二进制的快照只有摘要
+        let res = reqwest::get(format!("{}/logo", url)).await.unwrap();
+        assert_eq!(res.status(), 500);
+    }
+}
@@ -1,12 +1,37 @@
+pub mod assert;
+pub mod auth;
+pub mod client;
+mod cookie;
+mod curl;
+pub mod decoder;
+pub mod encoder;
+pub mod env;
+pub mod export;
+pub mod flow;
+pub mod har;
+mod inherit;
+pub mod mirror;
+pub mod mock;
+mod multipart;
+pub mod oauth2;
+pub mod openapi;
+pub mod proto;
+mod snapshot;
 pub mod xdiff;
 pub mod xreq;
 
-use std::str::FromStr;
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::Path,
+    str::FromStr,
+};
 
 use crate::ExtraArgs;
+use env::{interpolate_yaml, load_dotenv};
+use inherit::resolve_profiles;
 use reqwest::{
-    header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE},
-    Client, Method, Response,
+    header::{HeaderMap, HeaderName, HeaderValue, AUTHORIZATION, CONTENT_TYPE},
+    Client, Method, Request, Response, StatusCode,
 };
 use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use serde_json::json;
@@ -15,13 +40,19 @@ use url::Url;
 
 use crate::cli::parse_key_val;
 use crate::cli::KeyVal;
+use crate::jsonpath::JsonPath;
+use crate::report::OutputFormat;
 use anyhow::Result;
 use async_trait::async_trait;
+use export::ExportFormat;
 
-use clap::{Parser, Subcommand};
+use clap::{Parser, Subcommand, ValueEnum};
 
+use assert::AssertConfig;
+use auth::AuthConfig;
+use proto::ProtoConfig;
 use tokio::fs;
-pub use xdiff::ResponseProfile;
+pub use xdiff::{DiffMode, ResponseProfile};
 
 // load config from yaml file or string trait
 #[async_trait]
@@ -29,15 +60,41 @@ pub trait ConfigLoad
 where
     Self: Sized + ConfigValidate + DeserializeOwned,
 {
-    /// load yaml config from file
+    /// top level keys which are not profiles
+    const RESERVED_KEYS: &'static [&'static str] = &["client", "oauth2"];
+
+    /// resolve the relative paths in the config against the dir of the config file
+    fn resolve_paths(&mut self, _dir: &Path) {}
+
+    /// load yaml config from file, variables could be defined in the `.env` file
+    /// of the current dir or the config dir
     async fn load_yaml(path: &str) -> Result<Self> {
         let content = fs::read_to_string(path).await?;
-        Self::from_yaml(&content)
+        let mut vars = load_dotenv(".env")?;
+        let dir = Path::new(path).parent().unwrap_or_else(|| Path::new(""));
+        vars.extend(load_dotenv(dir.join(".env"))?);
+        Self::from_yaml_in_dir(&content, &vars, dir)
     }
 
     /// load yaml config from string
     fn from_yaml(content: &str) -> Result<Self> {
-        let config: Self = serde_yaml::from_str(content)?;
+        Self::from_yaml_with_vars(content, &HashMap::new())
+    }
+
+    /// load yaml config from string, `${VAR}` and `${VAR:-default}` in the string values
+    /// are replaced by the environment variables or the given vars
+    fn from_yaml_with_vars(content: &str, vars: &HashMap<String, String>) -> Result<Self> {
+        Self::from_yaml_in_dir(content, vars, Path::new(""))
+    }
+
+    /// load yaml config from string, relative paths are resolved against the dir
+    fn from_yaml_in_dir(content: &str, vars: &HashMap<String, String>, dir: &Path) -> Result<Self> {
+        let mut value: serde_yaml::Value = serde_yaml::from_str(content)?;
+        interpolate_yaml(&mut value, vars)?;
+        // 处理 defaults 和 extends，得到完整的 profile，继承关系的错误由 validate 报告
+        resolve_profiles(&mut value, Self::RESERVED_KEYS).validate()?;
+        let mut config: Self = serde_yaml::from_value(value)?;
+        config.resolve_paths(dir);
         // 需要使用validate方法来检查配置是否合法，所以Self需要实现ConfigValidate trait
         config.validate()?;
         Ok(config)
@@ -70,13 +127,53 @@ pub enum Action {
     Run(RunArgs),
     /// Parse the given url and name into a profile output
     Parse,
+    /// Run a proxy forwarding each request to the primary and candidate upstreams in the
+    /// `mirror` config, return the primary response and print the diff
+    Mirror(MirrorArgs),
+    /// Import the entries of a HAR file as profiles, req2 is sent to the `--base` url
+    Import(ImportArgs),
+}
+
+/// Send http requests based on the given profile and print the response
+#[derive(Debug, Clone, Parser)]
+pub struct ReqArgs {
+    #[clap(subcommand)]
+    pub action: ReqAction,
+}
+
+#[derive(Debug, Clone, Subcommand)]
+#[non_exhaustive]
+pub enum ReqAction {
+    /// Send the request of the given profile and print the response
+    Run(ReqRunArgs),
+    /// Parse the given url and name into a profile output
+    Parse,
+    /// Export the request of the given profile as a curl or httpie command, or raw http text
+    Export(ExportArgs),
+    /// Send the requests of the profiles and check the `assert` of each profile
+    Test(TestArgs),
+    /// Run the steps of the given flow in order, values captured from earlier responses
+    /// are used as `{{name}}` in later profiles
+    Flow(FlowArgs),
+    /// Start a mock http server answering the requests with the stubs in the config
+    Serve(ServeArgs),
+    /// Import the entries of a HAR file or the operations of an OpenAPI document as profiles
+    Import(ReqImportArgs),
 }
 
 #[derive(Debug, Clone, Parser)]
 pub struct RunArgs {
-    /// Profile name
-    #[clap(short, long, value_parser)]
-    pub profile: String,
+    /// Profile name, or a glob pattern like `todo*`
+    #[clap(short, long, value_parser, required_unless_present_any = ["all", "tag"])]
+    pub profile: Option<String>,
+
+    /// Run all the profiles in the config
+    #[clap(short, long)]
+    pub all: bool,
+
+    /// Run the profiles with the given tag
+    #[clap(short, long, value_parser, number_of_values = 1)]
+    pub tag: Vec<String>,
 
     /// Override args, Could be used to override the query, headers,and body of the request
     /// For query parameters: use `-e key=value`
@@ -88,6 +185,169 @@ pub struct RunArgs {
     /// Configuration to be used
     #[clap(short, long, value_parser)]
     pub config: Option<String>,
+
+    /// Diff mode, override the `mode` in the response profile
+    #[clap(short, long, value_enum)]
+    pub mode: Option<DiffMode>,
+
+    /// Output format of the diff result: text, json, patch or junit
+    #[clap(short, long, value_enum)]
+    pub output: Option<OutputFormat>,
+
+    /// Write the output to the given file instead of stdout
+    #[clap(long, value_parser)]
+    pub output_file: Option<String>,
+
+    /// Exit with 0 if the responses are identical, 1 if they differ and 2 on error
+    #[clap(long)]
+    pub exit_code: bool,
+
+    /// Record the live responses as the snapshots of the snapshot profiles, and show the
+    /// changes against the previous snapshots
+    #[clap(long)]
+    pub update: bool,
+}
+
+#[derive(Debug, Clone, Parser)]
+pub struct ReqRunArgs {
+    /// Profile name
+    #[clap(short, long, value_parser)]
+    pub profile: String,
+
+    /// Override args, the same as `xdiff run`
+    #[clap(short, long, value_parser=parse_key_val, number_of_values=1)]
+    pub extra_params: Vec<KeyVal>,
+
+    /// Configuration to be used
+    #[clap(short, long, value_parser)]
+    pub config: Option<String>,
+
+    /// Write the request and response to a HAR file
+    #[clap(long, value_parser)]
+    pub har: Option<String>,
+}
+
+#[derive(Debug, Clone, Parser)]
+pub struct TestArgs {
+    /// Profile name, or a glob pattern like `todo*`
+    #[clap(short, long, value_parser, required_unless_present = "all")]
+    pub profile: Option<String>,
+
+    /// Test all the profiles in the config
+    #[clap(short, long)]
+    pub all: bool,
+
+    /// Override args, the same as `xdiff run`
+    #[clap(short, long, value_parser=parse_key_val, number_of_values=1)]
+    pub extra_params: Vec<KeyVal>,
+
+    /// Configuration to be used
+    #[clap(short, long, value_parser)]
+    pub config: Option<String>,
+
+    /// Write the requests and responses to a HAR file
+    #[clap(long, value_parser)]
+    pub har: Option<String>,
+}
+
+#[derive(Debug, Clone, Parser)]
+pub struct FlowArgs {
+    /// Flow name
+    #[clap(short, long, value_parser)]
+    pub flow: String,
+
+    /// Override args, the same as `xdiff run`
+    #[clap(short, long, value_parser=parse_key_val, number_of_values=1)]
+    pub extra_params: Vec<KeyVal>,
+
+    /// Configuration to be used
+    #[clap(short, long, value_parser)]
+    pub config: Option<String>,
+}
+
+#[derive(Debug, Clone, Parser)]
+pub struct ServeArgs {
+    /// Configuration of the stubs, default to `./mock.yml`
+    #[clap(short, long, value_parser)]
+    pub config: Option<String>,
+
+    /// Address to listen on
+    #[clap(short, long, value_parser, default_value = "127.0.0.1:8080")]
+    pub addr: std::net::SocketAddr,
+}
+
+#[derive(Debug, Clone, Parser)]
+pub struct MirrorArgs {
+    /// Configuration with the `mirror` block, default to `./xdiff.yml`
+    #[clap(short, long, value_parser)]
+    pub config: Option<String>,
+
+    /// Address to listen on
+    #[clap(short, long, value_parser, default_value = "127.0.0.1:8081")]
+    pub addr: std::net::SocketAddr,
+
+    /// Diff mode, override the `mode` in the mirror config
+    #[clap(short, long, value_enum)]
+    pub mode: Option<DiffMode>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ImportFormat {
+    Har,
+    Openapi,
+}
+
+#[derive(Debug, Clone, Parser)]
+pub struct ImportArgs {
+    /// The HAR file to import
+    #[clap(short, long, value_parser)]
+    pub file: String,
+
+    /// Base url of req2, the path of each entry is appended to it
+    #[clap(short, long, value_parser)]
+    pub base: Url,
+
+    /// Write the config to the given file instead of stdout
+    #[clap(short, long, value_parser)]
+    pub output: Option<String>,
+}
+
+#[derive(Debug, Clone, Parser)]
+pub struct ReqImportArgs {
+    /// The HAR file or OpenAPI document to import
+    #[clap(short, long, value_parser)]
+    pub file: String,
+
+    /// Format of the file: a HAR archive, or an OpenAPI 3 document
+    #[clap(long, value_enum, default_value = "har")]
+    pub format: ImportFormat,
+
+    /// Override the server url of the OpenAPI document
+    #[clap(short, long, value_parser)]
+    pub base: Option<Url>,
+
+    /// Write the config to the given file instead of stdout
+    #[clap(short, long, value_parser)]
+    pub output: Option<String>,
+}
+
+#[derive(Debug, Clone, Parser)]
+pub struct ExportArgs {
+    /// Profile name
+    #[clap(short, long, value_parser)]
+    pub profile: String,
+
+    /// Override args, the same as the `run` action
+    #[clap(short, long, value_parser=parse_key_val, number_of_values=1)]
+    pub extra_params: Vec<KeyVal>,
+
+    /// Configuration to be used
+    #[clap(short, long, value_parser)]
+    pub config: Option<String>,
+
+    /// Export format: curl, httpie or http
+    #[clap(short, long, value_enum, default_value = "curl")]
+    pub format: ExportFormat,
 }
 
 // 如果是default 值则不序列化
@@ -99,6 +359,8 @@ fn is_default<T: PartialEq + Default>(v: &T) -> bool {
 pub struct RequestProfile {
     #[serde(with = "http_serde::method", default)]
     pub method: Method,
+    // 支持 flow 的 `{{name}}` 变量，包括 host 部分
+    #[serde(with = "flow::template_url")]
     pub url: Url,
     // 在默认没有传值的时候，不进行序列化
     #[serde(skip_serializing_if = "empty_json_value", default)]
@@ -112,27 +374,39 @@ pub struct RequestProfile {
     // 在默认没有传值的时候，不进行序列化
     #[serde(skip_serializing_if = "empty_json_value", default)]
     pub body: Option<serde_json::Value>,
+    // protobuf 的 body 和响应，需要指定 .proto 文件和 message 类型
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub proto: Option<ProtoConfig>,
+    // 认证方式，在合并完 ExtraArgs 之后作用于最终的请求
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub auth: Option<AuthConfig>,
+    // xreq test 时对响应的断言
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub assert: Option<AssertConfig>,
 }
 
 fn empty_json_value(val: &Option<serde_json::Value>) -> bool {
-    val.as_ref().map_or(true, |v| {
-        v.is_null() || (v.is_object() && v.as_object().unwrap().is_empty())
-    })
+    val.as_ref()
+        .is_none_or(|v| v.is_null() || (v.is_object() && v.as_object().unwrap().is_empty()))
 }
 
-// 对拿到的reqwest response 做了一次封装
+// 对拿到的reqwest response 做了一次封装，带上 profile 的 proto 配置用于解码 body
 #[derive(Debug)]
-pub struct ResponseExt(Response);
+pub struct ResponseExt(Response, Option<ProtoConfig>);
 
 impl FromStr for RequestProfile {
     type Err = anyhow::Error;
 
     fn from_str(s: &str) -> Result<Self> {
-        let mut url = Url::parse(s)?;
+        // 支持从浏览器中复制出来的 curl 命令
+        if RequestProfile::is_curl(s) {
+            return curl::parse_curl(s);
+        }
+        let mut url = Url::parse(s.trim())?;
         let querys = url.query_pairs();
         let mut params = json!({});
         for (key, value) in querys {
-            params[&*key] = value.parse()?
+            params[&*key] = parse_query_value(&value);
         }
         // url set query none
         url.set_query(None);
@@ -146,6 +420,30 @@ impl FromStr for RequestProfile {
     }
 }
 
+// query 中的值如果是合法的 json（比如数字，bool）则按 json 解析，否则作为字符串
+// query 和 form body 中的数组展开为同名的多个参数，比如 `tag=a&tag=b`
+pub(crate) fn query_pairs(query: &serde_json::Value) -> Vec<(String, String)> {
+    let to_string = |v: &serde_json::Value| match v {
+        serde_json::Value::String(s) => s.clone(),
+        serde_json::Value::Null => String::new(),
+        v => v.to_string(),
+    };
+    let mut pairs = vec![];
+    for (k, v) in query.as_object().into_iter().flatten() {
+        match v {
+            serde_json::Value::Array(items) => {
+                pairs.extend(items.iter().map(|item| (k.clone(), to_string(item))));
+            }
+            v => pairs.push((k.clone(), to_string(v))),
+        }
+    }
+    pairs
+}
+
+fn parse_query_value(value: &str) -> serde_json::Value {
+    value.parse().unwrap_or_else(|_| value.into())
+}
+
 impl RequestProfile {
     pub fn new(
         method: Method,
@@ -160,86 +458,159 @@ impl RequestProfile {
             params,
             headers,
             body,
+            proto: None,
+            auth: None,
+            assert: None,
         }
     }
-    pub async fn send(&self, args: &ExtraArgs) -> Result<ResponseExt> {
+    pub async fn send(&self, cli: &Client, args: &ExtraArgs) -> Result<ResponseExt> {
+        let req = self.build_request(cli, args)?;
+        self.execute(cli, req).await
+    }
+
+    /// build the request with the args merged and the auth applied
+    pub fn build_request(&self, cli: &Client, args: &ExtraArgs) -> Result<Request> {
         // args merge to self
         let (query, header, body) = self.generate(args)?;
-        // create client
-        let cli = Client::new();
+        // client 由调用方根据配置创建，一次运行中复用同一个 client
         // fill query, headers, and body
-        let req = cli
+        let mut req = cli
             .request(self.method.clone(), self.url.clone())
-            .query(&query)
+            .query(&query_pairs(&query))
             .headers(header)
             .body(body)
             .build()?;
+        // 认证（签名）需要覆盖最终的请求
+        if let Some(auth) = &self.auth {
+            auth.apply(&mut req)?;
+        } else if let Some(token) = &args.token {
+            // 全局的 oauth2 token 不覆盖 profile 或命令行中指定的 Authorization
+            if !req.headers().contains_key(AUTHORIZATION) {
+                let value = HeaderValue::from_str(&format!("Bearer {}", token))?;
+                req.headers_mut().insert(AUTHORIZATION, value);
+            }
+        }
+        Ok(req)
+    }
+
+    /// send the request built by `build_request`
+    pub async fn execute(&self, cli: &Client, req: Request) -> Result<ResponseExt> {
+        // digest 认证需要先拿到 401 响应中的 challenge，再带上 Authorization 重新发送
+        let retry = match &self.auth {
+            Some(auth @ AuthConfig::Digest { .. }) => req.try_clone().map(|r| (auth, r)),
+            _ => None,
+        };
         // send request
-        let res = cli.execute(req).await?;
+        let mut res = cli.execute(req).await?;
+        if let Some((auth, mut req)) = retry {
+            if res.status() == StatusCode::UNAUTHORIZED {
+                if let Some(value) = auth.digest_authorization(&req, res.headers())? {
+                    req.headers_mut().insert(AUTHORIZATION, value);
+                    res = cli.execute(req).await?;
+                }
+            }
+        }
 
         // get response
-        Ok(ResponseExt(res))
+        Ok(ResponseExt(res, self.proto.clone()))
     }
 
-    fn generate(&self, args: &ExtraArgs) -> Result<(serde_json::Value, HeaderMap, String)> {
-        // 拿到配置的profile 里面的 query， headers，和body， 然后将args 里面的 query， headers，body 的值 添加到req 的profile 中
+    fn generate(&self, args: &ExtraArgs) -> Result<(serde_json::Value, HeaderMap, Vec<u8>)> {
+        let (query, mut headers, body) = self.merge_args(args)?;
+        // 配置了 proto 的 request message 时，body 按 protobuf 编码
+        if let Some(proto) = &self.proto {
+            if let Some(data) = proto.encode(&body)? {
+                return Ok((query, headers, data));
+            }
+        }
+        // 根据content_type 选择 encoder 序列化body，没有指定时 merge_args 默认使用 application/json
+        let content_type =
+            get_content_type(&headers).unwrap_or_else(|| "application/json".to_string());
+        let encoder = encoder::get_encoder(&content_type)?;
+        // 没有配置 body 时，json 保持原来的 `{}`，其他类型发送空 body
+        if self.body.is_none() && content_type != "application/json" {
+            return Ok((query, headers, vec![]));
+        }
+        let body = encoder.encode(&body)?;
+        if let Some(content_type) = body.content_type {
+            headers.insert(CONTENT_TYPE, content_type.parse()?);
+        }
+        Ok((query, headers, body.data))
+    }
+
+    // 拿到配置的profile 里面的 query， headers，和body， 然后将args 里面的 query， headers，body 的值 添加到req 的profile 中
+    fn merge_args(
+        &self,
+        args: &ExtraArgs,
+    ) -> Result<(serde_json::Value, HeaderMap, serde_json::Value)> {
         let mut query = self.params.clone().unwrap_or_else(|| json!({}));
         let mut headers = self.headers.clone();
         let body = self.body.clone().unwrap_or_else(|| json!({}));
         // query add
         for (q_k, q_v) in &args.query {
             // parse 是从str 中转换成目标类型
-            query[q_k] = q_v.parse()?;
+            query[q_k] = parse_query_value(q_v);
         }
         // headers add
         for (h_k, h_v) in &args.headers {
             headers.insert(HeaderName::from_str(h_k)?, h_v.parse()?);
         }
-        // body add and serialize to string
-        // default add json serialize
+        // default add json serialize, protobuf body use application/x-protobuf
         if !headers.contains_key(CONTENT_TYPE) {
-            headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
-        }
-        // 获取content_type
-        let content_type = get_content_type(&headers);
-        // 根据content_type 序列化body
-        match content_type.as_deref() {
-            Some("application/json") => Ok((query, headers, serde_json::to_string(&body)?)),
-            Some("application/x-www-form-urlencoded" | "multipart/from-data") => {
-                Ok((query, headers, serde_urlencoded::to_string(&body)?))
-            }
-            Some("text/plain" | "ad-bill-pb/base64") => Ok((query, headers, body.to_string())),
-            // todo!() add other content-type support
-            _ => Err(anyhow::anyhow!("unsupport application type")),
+            let content_type = match &self.proto {
+                Some(ProtoConfig {
+                    request: Some(_), ..
+                }) => "application/x-protobuf",
+                _ => "application/json",
+            };
+            headers.insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
         }
+        Ok((query, headers, body))
     }
 
-    pub(crate) fn validate(&self) -> Result<()> {
-        let headers = self.headers.clone();
-        let content_type = get_content_type(&headers);
-        match content_type.as_deref() {
-            Some(
-                "application/json" | "application/x-www-form-urlencoded" | "multipart/from-data",
-            ) => {
-                if let Some(body) = &self.body {
-                    if !body.is_object() {
-                        return Err(anyhow::anyhow!(
-                            "Body must be an object: but got \n{}\n",
-                            serde_yaml::to_string(body)?
-                        ));
+    // 配置文件中 body 等引用的文件的相对路径相对于配置文件所在的目录
+    pub(crate) fn resolve_paths(&mut self, dir: &Path) {
+        if let Some(proto) = &mut self.proto {
+            proto.resolve_paths(dir);
+        }
+        if let Some(openapi) = self.assert.as_mut().and_then(|a| a.openapi.as_mut()) {
+            openapi.file = dir.join(&openapi.file);
+        }
+        if let Some(body) = &mut self.body {
+            match get_content_type(&self.headers).as_deref() {
+                Some("multipart/form-data") => multipart::resolve_files(body, dir),
+                Some("application/octet-stream") => {
+                    if let Some(path) = body.as_str().and_then(|s| s.strip_prefix('@')) {
+                        *body = format!("@{}", dir.join(path).display()).into();
                     }
                 }
+                _ => {}
             }
-            _ => {
+        }
+    }
+
+    pub(crate) fn validate(&self) -> Result<()> {
+        if let Some(proto) = &self.proto {
+            proto.validate()?;
+        }
+        if let Some(auth) = &self.auth {
+            auth.validate()?;
+        }
+        if let Some(assert) = &self.assert {
+            assert.validate()?;
+        }
+        if let Some(body) = &self.body {
+            // protobuf 的 body 需要能按 message 类型编码
+            if let Some(proto) = self.proto.as_ref().filter(|p| p.request.is_some()) {
+                proto.encode(body)?;
+            } else if let Some(content_type) = get_content_type(&self.headers) {
+                encoder::get_encoder(&content_type)?.validate(body)?;
+            } else if !body.is_string() {
                 // body is string
-                if let Some(body) = &self.body {
-                    if !body.is_string() {
-                        return Err(anyhow::anyhow!(
-                            "Body must be an string: but got \n{}\n",
-                            serde_yaml::to_string(body)?
-                        ));
-                    }
-                }
+                return Err(anyhow::anyhow!(
+                    "Body must be an string: but got \n{}\n",
+                    serde_yaml::to_string(body)?
+                ));
             }
         }
 
@@ -259,8 +630,13 @@ impl RequestProfile {
 fn get_content_type(headers: &HeaderMap) -> Option<String> {
     headers
         .get(CONTENT_TYPE)
-        .and_then(|v| v.to_str().unwrap().split(';').next())
-        .map(|v| v.to_string())
+        .and_then(|v| v.to_str().ok()?.split(';').next())
+        .map(|v| v.trim().to_lowercase())
+}
+
+// 完整的 content-type，包括 charset 等参数
+fn content_type_header(headers: &HeaderMap) -> Option<&str> {
+    headers.get(CONTENT_TYPE).and_then(|v| v.to_str().ok())
 }
 
 impl ResponseExt {
@@ -268,25 +644,172 @@ impl ResponseExt {
         self.0
     }
 
+    pub fn inner(&self) -> &Response {
+        &self.0
+    }
+
+    /// the syntax used to highlight the body, protobuf responses are shown as json
+    pub fn body_syntax(&self) -> &'static str {
+        match &self.1 {
+            Some(ProtoConfig {
+                response: Some(_), ..
+            }) if proto::is_protobuf(content_type_header(self.0.headers())) => "json",
+            _ => get_body_syntax(&self.0),
+        }
+    }
+
+    /// the body text of the response, structured bodies are pretty printed as json
+    pub async fn body_text(self, skip_body: &[String]) -> Result<String> {
+        match filter_body(self.body().await?, skip_body)? {
+            serde_json::Value::String(text) => Ok(text),
+            body => Ok(serde_json::to_string_pretty(&body)?),
+        }
+    }
+
+    async fn body(self) -> Result<serde_json::Value> {
+        let ResponseExt(res, proto) = self;
+        let headers = res.headers().clone();
+        let data = res.bytes().await?;
+        decode_response(proto.as_ref(), &headers, &data)
+    }
+
     pub fn get_header_keys(self) -> Vec<String> {
         let res_headers = self.0.headers();
         res_headers.iter().map(|(k, _)| k.to_string()).collect()
     }
     pub async fn filter_text(self, res: &ResponseProfile) -> Result<String> {
         // ResponseExt 里面是原始的请求，需要skip 的 阈 在res 中指定了，所以需要返回，res 中不skip 的 key 的值
-        let mut output_builder = Builder::default();
-        // let first_line = format!("{:?} {}\r\n", self.0.version(), self.0.status());
-        // output_builder.append(first_line);
-        // let output = get_header_text(self.0.headers(), &res.skip_headers)?;
-        // output_builder.append(output);
-        output_builder.append(get_status_text(&self.0)?);
-        output_builder.append(get_header_text(&self.0, &res.skip_headers)?);
-        output_builder.append(get_body_text(self.0, &res.skip_body).await?);
+        self.filter(res).await?.to_text()
+    }
+
+    // 根据 ResponseProfile 去除掉需要 skip 的 header 和 body，得到需要进行 diff 比较的响应
+    pub async fn filter(self, res: &ResponseProfile) -> Result<FilteredResponse> {
+        Ok(self.filter_raw(res).await?.1)
+    }
+
+    /// the filtered response together with the raw headers and body, e.g. to record a HAR
+    pub async fn filter_raw(
+        self,
+        res: &ResponseProfile,
+    ) -> Result<(RawResponse, FilteredResponse)> {
+        let ResponseExt(response, proto) = self;
+        let status = format!("{:?} {}", response.version(), response.status());
+        let headers = response.headers().clone();
+        let data = response.bytes().await?.to_vec();
+        let body = decode_response(proto.as_ref(), &headers, &data)?;
+        let filtered = FilteredResponse {
+            status: status.clone(),
+            headers: filter_headers(&headers, &res.skip_headers)?,
+            body: filter_body(body, &res.skip_body)?,
+        };
+        let raw = RawResponse {
+            status,
+            headers,
+            data,
+        };
+        Ok((raw, filtered))
+    }
+}
+
+// 配置了 proto 的 response message 时按 protobuf 解码，否则根据 content-type 解码
+fn decode_response(
+    proto: Option<&ProtoConfig>,
+    headers: &HeaderMap,
+    data: &[u8],
+) -> Result<serde_json::Value> {
+    let content_type = content_type_header(headers);
+    let decoded = proto.map(|p| p.decode(content_type, data));
+    if let Some(body) = decoded.transpose()?.flatten() {
+        return Ok(body);
+    }
+    decoder::decode_body(content_type, data)
+}
+
+// 读取之后的原始响应，同名的 header 没有合并
+#[derive(Debug, Clone)]
+pub struct RawResponse {
+    pub status: String,
+    pub headers: HeaderMap,
+    pub data: Vec<u8>,
+}
+
+fn filter_headers(
+    headers: &HeaderMap,
+    skip_headers: &[String],
+) -> Result<BTreeMap<String, String>> {
+    let mut output = BTreeMap::new();
+    for (k, v) in headers.iter() {
+        if skip_headers.contains(&k.to_string()) {
+            continue;
+        }
+        // 同名的 header（比如 set-cookie）合并到一起
+        output
+            .entry(k.to_string())
+            .and_modify(|val: &mut String| {
+                val.push_str(", ");
+                val.push_str(v.to_str().unwrap_or_default());
+            })
+            .or_insert(v.to_str()?.to_string());
+    }
+    Ok(output)
+}
+
+// 经过 ResponseProfile 过滤之后的响应，json 的 body 为 json value，其他的 body 为 string
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct FilteredResponse {
+    pub status: String,
+    pub headers: BTreeMap<String, String>,
+    pub body: serde_json::Value,
+}
+
+impl FilteredResponse {
+    /// filter a response whose body has already been read
+    pub fn from_parts(
+        status: String,
+        headers: &HeaderMap,
+        data: &[u8],
+        res: &ResponseProfile,
+    ) -> Result<Self> {
+        let body = decoder::decode_body(content_type_header(headers), data)?;
+        Ok(Self {
+            status,
+            headers: filter_headers(headers, &res.skip_headers)?,
+            body: filter_body(body, &res.skip_body)?,
+        })
+    }
 
+    pub fn to_text(&self) -> Result<String> {
+        let mut output_builder = Builder::default();
+        output_builder.append(format!("{}\r\n", self.status));
+        for (k, v) in &self.headers {
+            output_builder.append(format!("{}: {}\r\n", k, v));
+        }
+        output_builder.append("\r\n");
+        match &self.body {
+            serde_json::Value::Null => {}
+            serde_json::Value::String(text) => output_builder.append(text.as_str()),
+            // 按 key 排序后输出，响应中 key 的顺序不影响 diff
+            body => output_builder.append(serde_json::to_string_pretty(&sort_keys(body))?),
+        }
         Ok(output_builder.string()?)
     }
 }
 
+fn sort_keys(value: &serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(map) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            entries.sort_by(|a, b| a.0.cmp(b.0));
+            entries
+                .into_iter()
+                .map(|(k, v)| (k.clone(), sort_keys(v)))
+                .collect()
+        }
+        serde_json::Value::Array(items) => items.iter().map(sort_keys).collect(),
+        v => v.clone(),
+    }
+}
+
 pub fn get_status_text(res: &Response) -> Result<String> {
     Ok(format!("{:?} {}\r\n", res.version(), res.status()))
 }
@@ -306,34 +829,25 @@ pub fn get_header_text(res: &Response, skip_header: &[String]) -> Result<String>
 }
 
 pub async fn get_body_text(res: Response, skip_body: &[String]) -> Result<String> {
-    let mut output_builder = Builder::default();
-    let content_type = get_content_type(res.headers()).clone();
-    let text = res.text().await?;
-    // let ct = get_content_type(res_headers);
-    // 根据content_type 反序列化body
-    match content_type.as_deref() {
-        Some("application/json") => {
-            let output = filter_json_text(&text, skip_body)?;
-            output_builder.append(output);
-            Ok(output_builder.string()?)
-        }
+    // 根据content_type 解码body，二进制的 body 不会因为不是 utf8 而失败
+    ResponseExt(res, None).body_text(skip_body).await
+}
 
-        _ => {
-            // todo!() add other content-type support, now just return text
-            Ok(text)
-        }
-    }
+/// the syntax used to highlight the body of the response, e.g. `json`, `xml`
+pub fn get_body_syntax(res: &Response) -> &'static str {
+    decoder::body_syntax(content_type_header(res.headers()))
 }
 
-fn filter_json_text(text: &str, skip_body: &[String]) -> Result<String> {
-    let mut out_val: serde_json::Value = serde_json::from_str(text)?;
-    let res_val = out_val
-        .as_object_mut()
-        .ok_or_else(|| anyhow::anyhow!("unsupport json type"))?;
-    for key in skip_body {
-        res_val.remove(key);
+// 只有解码为结构化数据的 body 才能使用 json path 去除字段
+fn filter_body(mut body: serde_json::Value, skip_body: &[String]) -> Result<serde_json::Value> {
+    if body.is_string() {
+        return Ok(body);
     }
-    Ok(serde_json::to_string_pretty(&res_val)?)
+    // skip_body 中的每一项都是一个 json path，比如：`id`, `data.items[*].updated_at`, `$..trace_id`
+    for path in skip_body {
+        path.parse::<JsonPath>()?.remove(&mut body);
+    }
+    Ok(body)
 }
 
 #[cfg(test)]
@@ -377,6 +891,21 @@ mod tests {
         );
     }
 
+    #[test]
+    fn filter_body_should_skip_nested_path() {
+        let body =
+            json!({"id":1,"meta":{"trace_id":"abc"},"data":{"items":[{"id":1,"updated_at":2}]}});
+        let skip_body = vec![
+            "id".to_string(),
+            "data.items[*].updated_at".into(),
+            "$..trace_id".into(),
+        ];
+        assert_eq!(
+            filter_body(body, &skip_body).unwrap(),
+            json!({"meta": {}, "data": {"items": [{"id": 1}]}})
+        );
+    }
+
     #[tokio::test]
     async fn request_profile_send_should_work() {
         let _m = mock_for_url("/todo?a=1&b=2", "Get", json!({"id": 1, "name": "todo"}));
@@ -388,7 +917,11 @@ mod tests {
             HeaderMap::new(),
             None,
         );
-        let res = req.send(&Default::default()).await.unwrap().into_inner();
+        let res = req
+            .send(&Client::new(), &Default::default())
+            .await
+            .unwrap()
+            .into_inner();
         assert_eq!(res.status(), 200);
     }
 
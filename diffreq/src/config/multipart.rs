@@ -0,0 +1,307 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Context, Result};
+use serde_json::Value;
+
+// multipart/form-data 中的一个 part，body 中的每个字段对应一个或多个 part：
+// - `name: value` 文本 part
+// - `file: "@./path;type=image/png;filename=a.png"` 从文件中读取，写法和 curl -F 一致
+// - `name: {value: ..., content_type: ...}` / `file: {file: ./path, content_type: ..., filename: ...}`
+// - 数组表示同名的多个 part
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Part {
+    Text {
+        name: String,
+        value: String,
+        content_type: Option<String>,
+    },
+    File {
+        name: String,
+        path: PathBuf,
+        content_type: Option<String>,
+        filename: Option<String>,
+    },
+}
+
+/// parse the body object into multipart parts
+pub(crate) fn parse_parts(body: &Value) -> Result<Vec<Part>> {
+    let body = body
+        .as_object()
+        .ok_or_else(|| anyhow!("Multipart body must be an object"))?;
+    let mut parts = vec![];
+    for (name, value) in body {
+        match value {
+            Value::Array(values) => {
+                for v in values {
+                    parts.push(parse_part(name, v)?);
+                }
+            }
+            v => parts.push(parse_part(name, v)?),
+        }
+    }
+    Ok(parts)
+}
+
+fn parse_part(name: &str, value: &Value) -> Result<Part> {
+    let name = name.to_string();
+    match value {
+        Value::String(s) if s.starts_with('@') => {
+            let mut attrs = s[1..].split(';');
+            let path = PathBuf::from(attrs.next().unwrap_or_default());
+            let (mut content_type, mut filename) = (None, None);
+            for attr in attrs {
+                match attr.split_once('=') {
+                    Some(("type", v)) => content_type = Some(v.to_string()),
+                    Some(("filename", v)) => filename = Some(v.to_string()),
+                    _ => return Err(anyhow!("Invalid multipart file attribute: {}", attr)),
+                }
+            }
+            Ok(Part::File {
+                name,
+                path,
+                content_type,
+                filename,
+            })
+        }
+        Value::String(s) => Ok(Part::Text {
+            name,
+            value: s.clone(),
+            content_type: None,
+        }),
+        Value::Object(obj) => {
+            let get = |k: &str| obj.get(k).and_then(|v| v.as_str()).map(|v| v.to_string());
+            match (obj.get("file"), obj.get("value")) {
+                (Some(Value::String(path)), None) => Ok(Part::File {
+                    name,
+                    path: path.into(),
+                    content_type: get("content_type"),
+                    filename: get("filename"),
+                }),
+                (None, Some(v)) => Ok(Part::Text {
+                    name,
+                    value: v.as_str().map_or_else(|| v.to_string(), |s| s.to_string()),
+                    content_type: get("content_type"),
+                }),
+                _ => Err(anyhow!(
+                    "Multipart part {} must have either `file` or `value`",
+                    name
+                )),
+            }
+        }
+        Value::Null => Err(anyhow!("Multipart part {} must not be null", name)),
+        v => Ok(Part::Text {
+            name,
+            value: v.to_string(),
+            content_type: None,
+        }),
+    }
+}
+
+/// validate the parts of the body, all the files must exist
+pub(crate) fn validate_multipart(body: &Value) -> Result<()> {
+    for part in parse_parts(body)? {
+        if let Part::File { name, path, .. } = part {
+            if !path.is_file() {
+                return Err(anyhow!(
+                    "Multipart part {}: file {} not found",
+                    name,
+                    path.display()
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// resolve the relative file paths of the parts against the dir of the config file
+pub(crate) fn resolve_files(body: &mut Value, dir: &Path) {
+    let Some(body) = body.as_object_mut() else {
+        return;
+    };
+    for value in body.values_mut() {
+        match value {
+            Value::Array(values) => values.iter_mut().for_each(|v| resolve_file(v, dir)),
+            v => resolve_file(v, dir),
+        }
+    }
+}
+
+fn resolve_file(value: &mut Value, dir: &Path) {
+    let join = |path: &str| dir.join(path).to_string_lossy().into_owned();
+    match value {
+        // `@path;type=...` 只替换路径部分，保留后面的属性
+        Value::String(s) if s.starts_with('@') => {
+            let (path, attrs) = s[1..].split_once(';').unwrap_or((&s[1..], ""));
+            let mut resolved = format!("@{}", join(path));
+            if !attrs.is_empty() {
+                resolved.push(';');
+                resolved.push_str(attrs);
+            }
+            *s = resolved;
+        }
+        Value::Object(obj) if obj.get("value").is_none() => {
+            if let Some(Value::String(path)) = obj.get_mut("file") {
+                *path = join(path);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// encode the body object into multipart bytes, return the boundary and the body
+pub(crate) fn encode_multipart(body: &Value) -> Result<(String, Vec<u8>)> {
+    let boundary = gen_boundary();
+    let mut output = vec![];
+    for part in parse_parts(body)? {
+        output.extend_from_slice(format!("--{}\r\n", boundary).as_bytes());
+        let (data, content_type) = match part {
+            Part::Text {
+                name,
+                value,
+                content_type,
+            } => {
+                output.extend_from_slice(
+                    format!(
+                        "Content-Disposition: form-data; name=\"{}\"\r\n",
+                        escape_quote(&name)
+                    )
+                    .as_bytes(),
+                );
+                (value.into_bytes(), content_type)
+            }
+            Part::File {
+                name,
+                path,
+                content_type,
+                filename,
+            } => {
+                let data = std::fs::read(&path)
+                    .with_context(|| format!("read multipart file: {}", path.display()))?;
+                let filename = filename.unwrap_or_else(|| file_name(&path));
+                output.extend_from_slice(
+                    format!(
+                        "Content-Disposition: form-data; name=\"{}\"; filename=\"{}\"\r\n",
+                        escape_quote(&name),
+                        escape_quote(&filename)
+                    )
+                    .as_bytes(),
+                );
+                let content_type = content_type.unwrap_or_else(|| {
+                    mime_guess::from_path(&path)
+                        .first_or_octet_stream()
+                        .to_string()
+                });
+                (data, Some(content_type))
+            }
+        };
+        if let Some(content_type) = content_type {
+            output.extend_from_slice(format!("Content-Type: {}\r\n", content_type).as_bytes());
+        }
+        output.extend_from_slice(b"\r\n");
+        output.extend_from_slice(&data);
+        output.extend_from_slice(b"\r\n");
+    }
+    output.extend_from_slice(format!("--{}--\r\n", boundary).as_bytes());
+    Ok((boundary, output))
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|f| f.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+fn escape_quote(s: &str) -> String {
+    s.replace('"', "%22")
+}
+
+// 根据时间和计数器生成 boundary，同一个进程内的 boundary 不会重复
+fn gen_boundary() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    format!(
+        "------------------------xreq{:x}{:04x}",
+        nanos,
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::temp_file;
+    use serde_json::json;
+
+    #[test]
+    fn encode_multipart_should_work() {
+        let (_dir, path) = temp_file("hello.txt", "hello");
+        let body = json!({
+            "name": "todo",
+            "file": format!("@{};type=text/plain", path.display()),
+            "meta": {"value": {"a": 1}, "content_type": "application/json"},
+        });
+        validate_multipart(&body).unwrap();
+        let (boundary, data) = encode_multipart(&body).unwrap();
+        let expected = format!(
+            "--{b}\r\nContent-Disposition: form-data; name=\"name\"\r\n\r\ntodo\r\n\
+             --{b}\r\nContent-Disposition: form-data; name=\"file\"; filename=\"hello.txt\"\r\nContent-Type: text/plain\r\n\r\nhello\r\n\
+             --{b}\r\nContent-Disposition: form-data; name=\"meta\"\r\nContent-Type: application/json\r\n\r\n{{\"a\":1}}\r\n--{b}--\r\n",
+            b = boundary
+        );
+        assert_eq!(String::from_utf8(data).unwrap(), expected);
+
+        let body = json!({"file": "@./not_exists.txt"});
+        assert!(validate_multipart(&body).is_err());
+    }
+
+    #[test]
+    fn resolve_files_should_join_config_dir() {
+        let mut body = json!({
+            "name": "todo",
+            "file": "@a.png;type=image/png",
+            "files": ["@b.txt", {"file": "c.txt", "filename": "c"}],
+            "meta": {"value": "@not_a_file"},
+        });
+        resolve_files(&mut body, Path::new("/config"));
+        assert_eq!(
+            body,
+            json!({
+                "name": "todo",
+                "file": "@/config/a.png;type=image/png",
+                "files": ["@/config/b.txt", {"file": "/config/c.txt", "filename": "c"}],
+                "meta": {"value": "@not_a_file"},
+            })
+        );
+    }
+
+    #[test]
+    fn multipart_files_should_be_relative_to_config_dir() {
+        use crate::{ConfigLoad, RequestConfig};
+
+        let (dir, path) = temp_file("hello.txt", "hello");
+        let content = r#"
+upload:
+  method: POST
+  url: https://example.com/upload
+  headers:
+    content-type: multipart/form-data
+  body:
+    file: "@hello.txt;type=text/plain"
+"#;
+        let config =
+            RequestConfig::from_yaml_in_dir(content, &Default::default(), dir.path()).unwrap();
+        let body = config.profiles["upload"].body.as_ref().unwrap();
+        assert_eq!(
+            body["file"],
+            json!(format!("@{};type=text/plain", path.display()))
+        );
+        assert!(RequestConfig::from_yaml(content).is_err());
+    }
+}
@@ -0,0 +1,322 @@
+use std::{
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Context, Result};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use url::Url;
+
+use super::{is_default, ConfigValidate};
+
+// access token 提前这么多秒视为过期，避免请求发送过程中过期
+const EXPIRY_SKEW_SECS: u64 = 30;
+
+// 所有 profile 共用的 oauth2 配置，获取的 access token 以 `Authorization: Bearer <token>` 的方式发送，
+// profile 自己配置了认证时不覆盖。设置 `cache: true` 时 token 缓存到用户的缓存目录中直到过期
+// ```yaml
+// oauth2:
+//   token_url: https://auth.example.com/oauth/token
+//   client_id: ${CLIENT_ID}
+//   client_secret: ${CLIENT_SECRET}
+//   scope: todo.read
+//   cache: true
+// ```
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OAuth2Config {
+    pub token_url: Url,
+    pub client_id: String,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub client_secret: Option<String>,
+    #[serde(skip_serializing_if = "is_default", default)]
+    pub grant_type: GrantType,
+    /// required by the `refresh_token` grant
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub refresh_token: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub scope: Option<String>,
+    /// how the client credentials are sent to the token endpoint
+    #[serde(skip_serializing_if = "is_default", default)]
+    pub client_auth: ClientAuth,
+    /// cache the token on disk until it expires, only readable by the current user
+    #[serde(skip_serializing_if = "is_default", default)]
+    pub cache: bool,
+    /// token cache file, default to a file in `$XDG_CACHE_HOME/xreq` (or the os cache dir),
+    /// setting it also enables the cache
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub cache_file: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GrantType {
+    #[default]
+    ClientCredentials,
+    RefreshToken,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClientAuth {
+    /// http basic auth (client_secret_basic)
+    #[default]
+    Basic,
+    /// client_id and client_secret in the form body (client_secret_post)
+    Body,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct CachedToken {
+    access_token: String,
+    // unix 时间戳（秒），没有 expires_in 时一直有效
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    expires_at: Option<u64>,
+    // 服务端轮换 refresh token 时保存新的 refresh token
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    refresh_token: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    #[serde(default)]
+    expires_in: Option<u64>,
+    #[serde(default)]
+    refresh_token: Option<String>,
+}
+
+impl OAuth2Config {
+    /// resolve the relative cache file against the dir of the config file
+    pub fn resolve_paths(&mut self, dir: &Path) {
+        if let Some(path) = &mut self.cache_file {
+            *path = dir.join(&*path);
+        }
+    }
+
+    /// the cached access token if not expired, otherwise fetch a new one
+    pub async fn token(&self, client: &Client) -> Result<String> {
+        let cache = match self.cache_path()? {
+            Some(cache) => cache,
+            None => return Ok(self.fetch(client, None).await?.access_token),
+        };
+        let cached = load_cache(&cache);
+        if let Some(token) = &cached {
+            if token
+                .expires_at
+                .is_none_or(|t| t > now() + EXPIRY_SKEW_SECS)
+            {
+                return Ok(token.access_token.clone());
+            }
+        }
+        let refresh_token = cached.and_then(|t| t.refresh_token);
+        let token = self.fetch(client, refresh_token).await?;
+        save_cache(&cache, &token)
+            .with_context(|| format!("write oauth2 token cache: {}", cache.display()))?;
+        Ok(token.access_token)
+    }
+
+    async fn fetch(&self, client: &Client, refresh_token: Option<String>) -> Result<CachedToken> {
+        let mut form = vec![];
+        match self.grant_type {
+            GrantType::ClientCredentials => form.push(("grant_type", "client_credentials".into())),
+            GrantType::RefreshToken => {
+                let token = refresh_token
+                    .or_else(|| self.refresh_token.clone())
+                    .ok_or_else(|| anyhow!("refresh_token grant requires a refresh_token"))?;
+                form.push(("grant_type", "refresh_token".into()));
+                form.push(("refresh_token", token));
+            }
+        }
+        if let Some(scope) = &self.scope {
+            form.push(("scope", scope.clone()));
+        }
+        let mut req = client.post(self.token_url.clone());
+        match (&self.client_secret, self.client_auth) {
+            (Some(secret), ClientAuth::Basic) => {
+                req = req.basic_auth(&self.client_id, Some(secret));
+            }
+            (secret, _) => {
+                form.push(("client_id", self.client_id.clone()));
+                if let Some(secret) = secret {
+                    form.push(("client_secret", secret.clone()));
+                }
+            }
+        }
+        let res = req
+            .form(&form)
+            .send()
+            .await
+            .with_context(|| format!("request oauth2 token: {}", self.token_url))?;
+        let status = res.status();
+        if !status.is_success() {
+            let text = res.text().await.unwrap_or_default();
+            return Err(anyhow!(
+                "oauth2 token endpoint returned {}: {}",
+                status,
+                text
+            ));
+        }
+        let body = res.bytes().await?;
+        let res: TokenResponse =
+            serde_json::from_slice(&body).context("parse oauth2 token response")?;
+        Ok(CachedToken {
+            access_token: res.access_token,
+            expires_at: res.expires_in.map(|s| now() + s),
+            refresh_token: res.refresh_token,
+        })
+    }
+
+    // 没有开启缓存时返回 None
+    fn cache_path(&self) -> Result<Option<PathBuf>> {
+        if let Some(path) = &self.cache_file {
+            return Ok(Some(path.clone()));
+        }
+        if !self.cache {
+            return Ok(None);
+        }
+        let dir = dirs::cache_dir().ok_or_else(|| anyhow!("no cache dir for oauth2 token"))?;
+        // 不同的 token endpoint、client 和 scope 使用不同的缓存文件
+        let key = format!(
+            "{}\n{}\n{:?}\n{}",
+            self.token_url,
+            self.client_id,
+            self.grant_type,
+            self.scope.as_deref().unwrap_or_default()
+        );
+        let hash = Sha256::digest(key.as_bytes());
+        let name: String = hash[..8].iter().map(|b| format!("{:02x}", b)).collect();
+        Ok(Some(dir.join("xreq").join(format!("oauth2-{}.json", name))))
+    }
+}
+
+// 缓存文件损坏时当作没有缓存
+fn load_cache(path: &Path) -> Option<CachedToken> {
+    let data = fs::read(path).ok()?;
+    serde_json::from_slice(&data).ok()
+}
+
+// token 只允许当前用户读写，目录为 0700，文件为 0600
+fn save_cache(path: &Path, token: &CachedToken) -> Result<()> {
+    let mut dir_builder = fs::DirBuilder::new();
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+        dir_builder.mode(0o700);
+        options.mode(0o600);
+    }
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        dir_builder.recursive(true).create(dir)?;
+    }
+    let mut file = options.open(path)?;
+    // 已经存在的文件不受 mode 的影响
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    }
+    file.write_all(&serde_json::to_vec_pretty(token)?)?;
+    Ok(())
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+impl ConfigValidate for OAuth2Config {
+    fn validate(&self) -> Result<()> {
+        if self.client_id.is_empty() {
+            return Err(anyhow!("client_id must not be empty"));
+        }
+        if self.grant_type == GrantType::RefreshToken && self.refresh_token.is_none() {
+            return Err(anyhow!("refresh_token grant requires a refresh_token"));
+        }
+        if let Some(path) = &self.cache_file {
+            if path.is_dir() {
+                return Err(anyhow!("cache_file: {} is a directory", path.display()));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mockito::Matcher;
+
+    #[tokio::test]
+    async fn oauth2_token_should_be_cached() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = dir.path().join("token.json");
+        let _m = mockito::mock("POST", "/oauth/token")
+            .match_header("authorization", "Basic YXBwOnNlY3JldA==")
+            .match_body(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("grant_type".into(), "client_credentials".into()),
+                Matcher::UrlEncoded("scope".into(), "todo.read".into()),
+            ]))
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"access_token":"t1","token_type":"Bearer","expires_in":3600}"#)
+            .expect(1)
+            .create();
+        let config: OAuth2Config = serde_yaml::from_str(&format!(
+            "token_url: {}/oauth/token\nclient_id: app\nclient_secret: secret\nscope: todo.read\ncache_file: {}",
+            mockito::server_url(),
+            cache.display()
+        ))
+        .unwrap();
+        config.validate().unwrap();
+        let client = Client::new();
+        assert_eq!(config.token(&client).await.unwrap(), "t1");
+        // 第二次直接使用缓存的 token
+        assert_eq!(config.token(&client).await.unwrap(), "t1");
+        _m.assert();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&cache).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        // 默认不缓存
+        let config = OAuth2Config {
+            cache_file: None,
+            ..config
+        };
+        assert_eq!(config.cache_path().unwrap(), None);
+        let config = OAuth2Config {
+            cache: true,
+            ..config
+        };
+        let path = config.cache_path().unwrap().unwrap();
+        assert!(path.starts_with(dirs::cache_dir().unwrap().join("xreq")));
+    }
+
+    #[test]
+    fn oauth2_cache_file_should_be_relative_to_config_dir() {
+        use crate::{ConfigLoad, RequestConfig};
+
+        let dir = tempfile::tempdir().unwrap();
+        let content = r#"
+oauth2:
+  token_url: https://example.com/oauth/token
+  client_id: app
+  client_secret: secret
+  cache_file: token.json
+todo:
+  url: https://jsonplaceholder.typicode.com/todos/1
+"#;
+        let config =
+            RequestConfig::from_yaml_in_dir(content, &Default::default(), dir.path()).unwrap();
+        let oauth2 = config.oauth2.unwrap();
+        assert_eq!(oauth2.cache_file, Some(dir.path().join("token.json")));
+    }
+}
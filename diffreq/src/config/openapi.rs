@@ -0,0 +1,766 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{Arc, Mutex, OnceLock},
+};
+
+use anyhow::{anyhow, Context, Result};
+use regex::Regex;
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE},
+    Method,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use url::Url;
+
+use super::{
+    assert::{AssertConfig, JsonType},
+    xreq::RequestConfig,
+    ConfigValidate, RequestProfile,
+};
+use crate::util::{encode_uri_component, profile_name, unique_name};
+
+const METHODS: [&str; 8] = [
+    "get", "put", "post", "delete", "options", "head", "patch", "trace",
+];
+// 防止循环引用的 schema 无限展开
+const MAX_DEPTH: usize = 32;
+
+// profile 的 assert 中引用 openapi 文档中的 operation，按响应的 status 找到响应的 schema 校验 body
+// ```yaml
+// assert:
+//   openapi:
+//     file: ./openapi.yml
+//     operation: getTodo
+// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OpenApiAssert {
+    pub file: PathBuf,
+    /// the `operationId`, or `<METHOD> <path>` like `GET /todos/{id}`
+    pub operation: String,
+}
+
+/// an OpenAPI 3 document in yaml or json
+#[derive(Debug, Clone)]
+pub struct OpenApi(Value);
+
+// 文档中的一个 operation
+struct Operation<'a> {
+    method: &'a str,
+    path: &'a str,
+    item: &'a Value,
+    op: &'a Value,
+}
+
+impl OpenApi {
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("read openapi: {}", path.display()))?;
+        Self::from_str(&content).with_context(|| format!("parse openapi: {}", path.display()))
+    }
+
+    /// load the document once per run, profiles asserting the same file share it
+    pub fn load_cached(path: impl AsRef<Path>) -> Result<Arc<Self>> {
+        static DOCS: OnceLock<Mutex<HashMap<PathBuf, Arc<OpenApi>>>> = OnceLock::new();
+        let path = path.as_ref();
+        let docs = DOCS.get_or_init(Default::default);
+        if let Some(doc) = docs.lock().unwrap().get(path) {
+            return Ok(doc.clone());
+        }
+        // 加载失败的不缓存
+        let doc = Arc::new(Self::load(path)?);
+        docs.lock().unwrap().insert(path.to_path_buf(), doc.clone());
+        Ok(doc)
+    }
+
+    /// one profile per operation, the params and bodies are filled with the examples in the
+    /// document, and the responses are validated by the response schemas
+    pub fn to_request_config(&self, file: &Path, base: Option<&Url>) -> Result<RequestConfig> {
+        let server = match base {
+            Some(base) => base.as_str().to_string(),
+            None => self.server()?,
+        };
+        let mut names = HashSet::new();
+        let mut profiles = HashMap::new();
+        for operation in self.operations() {
+            let id = operation.op["operationId"]
+                .as_str()
+                .map(String::from)
+                .unwrap_or_else(|| operation.id());
+            let profile = self
+                .to_profile(&server, &operation)
+                .with_context(|| format!("operation: {}", id))?;
+            let name = match operation.op["operationId"].as_str() {
+                Some(id) => snake_case(id),
+                None => profile_name(operation.method, operation.path),
+            };
+            let profile = RequestProfile {
+                assert: Some(AssertConfig {
+                    openapi: Some(OpenApiAssert {
+                        file: file.to_path_buf(),
+                        operation: id,
+                    }),
+                    ..Default::default()
+                }),
+                ..profile
+            };
+            profiles.insert(unique_name(&mut names, &name), profile);
+        }
+        Ok(RequestConfig::new(profiles))
+    }
+
+    /// validate the body by the response schema of the operation, return the violations
+    pub fn check(&self, operation: &str, status: u16, body: &Value) -> Result<Vec<String>> {
+        let operation = self
+            .operation(operation)
+            .ok_or_else(|| anyhow!("operation {} not found", operation))?;
+        let responses = &operation.op["responses"];
+        let code = status.to_string();
+        let range = format!("{}XX", status / 100);
+        let response = [code.as_str(), range.as_str(), "default"]
+            .iter()
+            .find_map(|k| responses.get(k))
+            .ok_or_else(|| anyhow!("status {} is not documented", status))?;
+        let schema = self
+            .media(&self.resolve(response)["content"])
+            .and_then(|(_, media)| media.get("schema"));
+        let mut errors = vec![];
+        if let Some(schema) = schema {
+            self.validate(schema, body, "$", &mut errors, 0);
+        }
+        Ok(errors)
+    }
+
+    fn server(&self) -> Result<String> {
+        let server = &self.0["servers"][0];
+        let mut url = server["url"].as_str().unwrap_or_default().to_string();
+        // `{scheme}://example.com` 之类的变量使用默认值
+        if let Some(vars) = server["variables"].as_object() {
+            for (name, var) in vars {
+                let value = var["default"].as_str().unwrap_or_default();
+                url = url.replace(&format!("{{{}}}", name), value);
+            }
+        }
+        Url::parse(&url)
+            .map(|_| url.clone())
+            .map_err(|_| anyhow!("server url `{}` is not absolute, use --base", url))
+    }
+
+    fn operations(&self) -> Vec<Operation<'_>> {
+        let mut operations = vec![];
+        for (path, item) in self.0["paths"].as_object().into_iter().flatten() {
+            let item = self.resolve(item);
+            for method in METHODS {
+                if let Some(op) = item.get(method) {
+                    operations.push(Operation {
+                        method,
+                        path,
+                        item,
+                        op,
+                    });
+                }
+            }
+        }
+        operations
+    }
+
+    fn operation(&self, id: &str) -> Option<Operation<'_>> {
+        self.operations()
+            .into_iter()
+            .find(|o| o.op["operationId"].as_str() == Some(id) || o.id() == id)
+    }
+
+    fn to_profile(&self, server: &str, operation: &Operation) -> Result<RequestProfile> {
+        let mut path = operation.path.to_string();
+        let mut params = Map::new();
+        let mut headers = HeaderMap::new();
+        for param in self.parameters(operation) {
+            let name = param["name"].as_str().unwrap_or_default();
+            let value = self.media_example(param);
+            let text = match &value {
+                Value::String(s) => s.clone(),
+                v => v.to_string(),
+            };
+            match param["in"].as_str() {
+                Some("path") => {
+                    let value = encode_uri_component(&text);
+                    path = path.replace(&format!("{{{}}}", name), &value);
+                }
+                // 可选的 query 参数只在有示例时填充
+                Some("query")
+                    if param["required"] == json!(true) || param.get("example").is_some() =>
+                {
+                    params.insert(name.to_string(), value);
+                }
+                Some("header") => {
+                    headers.insert(HeaderName::from_str(name)?, HeaderValue::from_str(&text)?);
+                }
+                _ => {}
+            }
+        }
+
+        let url = Url::parse(&format!("{}{}", server.trim_end_matches('/'), path))?;
+        let body = self.resolve(&operation.op["requestBody"]);
+        let body = match self.media(&body["content"]) {
+            Some((content_type, media)) => {
+                headers.insert(CONTENT_TYPE, HeaderValue::from_str(content_type)?);
+                Some(self.media_example(media))
+            }
+            None => None,
+        };
+        let method = Method::from_str(&operation.method.to_uppercase())?;
+        let params = (!params.is_empty()).then_some(Value::Object(params));
+        Ok(RequestProfile::new(method, url, params, headers, body))
+    }
+
+    // path item 和 operation 中的参数，operation 中同名的参数覆盖 path item 中的
+    fn parameters<'a>(&'a self, operation: &Operation<'a>) -> Vec<&'a Value> {
+        let mut params: Vec<&Value> = vec![];
+        let all = [&operation.item["parameters"], &operation.op["parameters"]];
+        for param in all.into_iter().filter_map(|p| p.as_array()).flatten() {
+            let param = self.resolve(param);
+            params.retain(|p| p["name"] != param["name"] || p["in"] != param["in"]);
+            params.push(param);
+        }
+        params
+    }
+
+    // 优先使用 json 的 media type
+    fn media<'a>(&self, content: &'a Value) -> Option<(&'a str, &'a Value)> {
+        let content = content.as_object()?;
+        content
+            .iter()
+            .find(|(k, _)| k.starts_with("application/json") || k.ends_with("+json"))
+            .or_else(|| content.iter().next())
+            .map(|(k, v)| (k.as_str(), v))
+    }
+
+    fn media_example(&self, media: &Value) -> Value {
+        if let Some(example) = media.get("example") {
+            return example.clone();
+        }
+        if let Some(example) = media["examples"]
+            .as_object()
+            .and_then(|e| e.values().next())
+            .and_then(|e| self.resolve(e).get("value"))
+        {
+            return example.clone();
+        }
+        media
+            .get("schema")
+            .and_then(|s| self.example(s, &mut vec![]))
+            .unwrap_or(Value::Null)
+    }
+
+    // 根据 schema 生成示例，refs 是正在展开的 $ref，再次遇到时说明 schema 引用了自身，
+    // 返回 None 不再展开
+    fn example(&self, schema: &Value, refs: &mut Vec<String>) -> Option<Value> {
+        let reference = schema["$ref"].as_str();
+        if let Some(r) = reference {
+            if refs.iter().any(|seen| seen == r) {
+                return None;
+            }
+            refs.push(r.to_string());
+        }
+        let value = self.schema_example(self.resolve(schema), refs);
+        if reference.is_some() {
+            refs.pop();
+        }
+        Some(value)
+    }
+
+    // 优先使用 schema 中的 example，default 和 enum
+    fn schema_example(&self, schema: &Value, refs: &mut Vec<String>) -> Value {
+        for key in ["example", "default", "const"] {
+            if let Some(value) = schema.get(key) {
+                return value.clone();
+            }
+        }
+        if let Some(value) = schema["enum"].as_array().and_then(|e| e.first()) {
+            return value.clone();
+        }
+        if let Some(all) = schema["allOf"].as_array() {
+            let mut merged = Map::new();
+            for s in all {
+                if let Some(Value::Object(map)) = self.example(s, refs) {
+                    merged.extend(map);
+                }
+            }
+            return Value::Object(merged);
+        }
+        for key in ["oneOf", "anyOf"] {
+            if let Some(s) = schema[key].as_array().and_then(|s| s.first()) {
+                return self.example(s, refs).unwrap_or(Value::Null);
+            }
+        }
+        let kind = schema_types(schema)
+            .into_iter()
+            .find(|t| *t != "null")
+            .or_else(|| schema.get("properties").map(|_| "object"));
+        match kind {
+            // 递归引用自身的属性和数组元素直接省略
+            Some("object") => {
+                let properties = schema["properties"].as_object().into_iter().flatten();
+                Value::Object(
+                    properties
+                        .filter_map(|(k, s)| Some((k.clone(), self.example(s, refs)?)))
+                        .collect(),
+                )
+            }
+            Some("array") => {
+                Value::Array(self.example(&schema["items"], refs).into_iter().collect())
+            }
+            Some("string") => json!(match schema["format"].as_str() {
+                Some("date-time") => "2024-01-01T00:00:00Z",
+                Some("date") => "2024-01-01",
+                Some("email") => "user@example.com",
+                Some("uuid") => "00000000-0000-0000-0000-000000000000",
+                Some("uri") | Some("url") => "https://example.com",
+                _ => "string",
+            }),
+            Some("integer") => schema.get("minimum").cloned().unwrap_or(json!(1)),
+            Some("number") => schema.get("minimum").cloned().unwrap_or(json!(1.0)),
+            Some("boolean") => json!(true),
+            _ => Value::Null,
+        }
+    }
+
+    // 支持 OpenAPI 3.0 和 3.1 中常用的 JSON Schema 关键字
+    fn validate(
+        &self,
+        schema: &Value,
+        value: &Value,
+        path: &str,
+        errors: &mut Vec<String>,
+        depth: usize,
+    ) {
+        let schema = self.resolve(schema);
+        if depth > MAX_DEPTH || !schema.is_object() {
+            return;
+        }
+        let types = schema_types(schema);
+        if value.is_null() && (schema["nullable"] == json!(true) || types.contains(&"null")) {
+            return;
+        }
+        for s in schema["allOf"].as_array().into_iter().flatten() {
+            self.validate(s, value, path, errors, depth + 1);
+        }
+        // anyOf 至少匹配一个，oneOf 必须正好匹配一个
+        for key in ["anyOf", "oneOf"] {
+            if let Some(schemas) = schema[key].as_array() {
+                let matched = schemas
+                    .iter()
+                    .filter(|s| {
+                        let mut errors = vec![];
+                        self.validate(s, value, path, &mut errors, depth + 1);
+                        errors.is_empty()
+                    })
+                    .count();
+                if matched == 0 {
+                    errors.push(format!("{}: does not match any schema of {}", path, key));
+                } else if matched > 1 && key == "oneOf" {
+                    errors.push(format!(
+                        "{}: matches {} schemas of oneOf, expected exactly one",
+                        path, matched
+                    ));
+                }
+            }
+        }
+        let actual = JsonType::of(value);
+        if !types.is_empty() {
+            let matched = types.iter().any(|t| {
+                serde_json::from_value::<JsonType>(json!(t)).is_ok_and(|t| t.matches(value))
+            });
+            if !matched {
+                errors.push(format!(
+                    "{}: expected type {}, got {}",
+                    path,
+                    types.join(" or "),
+                    actual
+                ));
+                return;
+            }
+        }
+        if let Some(values) = schema["enum"].as_array() {
+            if !values.contains(value) {
+                errors.push(format!(
+                    "{}: {} is not one of {}",
+                    path, value, schema["enum"]
+                ));
+            }
+        }
+
+        match value {
+            Value::String(s) => {
+                let len = s.chars().count() as u64;
+                if schema["minLength"].as_u64().is_some_and(|min| len < min) {
+                    errors.push(format!("{}: shorter than {}", path, schema["minLength"]));
+                }
+                if schema["maxLength"].as_u64().is_some_and(|max| len > max) {
+                    errors.push(format!("{}: longer than {}", path, schema["maxLength"]));
+                }
+                if let Some(pattern) = schema["pattern"].as_str() {
+                    if Regex::new(pattern).is_ok_and(|re| !re.is_match(s)) {
+                        errors.push(format!("{}: {} does not match {}", path, value, pattern));
+                    }
+                }
+            }
+            Value::Number(n) => {
+                let n = n.as_f64().unwrap_or_default();
+                if schema["minimum"].as_f64().is_some_and(|min| n < min) {
+                    errors.push(format!("{}: less than {}", path, schema["minimum"]));
+                }
+                if schema["maximum"].as_f64().is_some_and(|max| n > max) {
+                    errors.push(format!("{}: greater than {}", path, schema["maximum"]));
+                }
+            }
+            Value::Array(items) => {
+                let len = items.len() as u64;
+                if schema["minItems"].as_u64().is_some_and(|min| len < min) {
+                    errors.push(format!("{}: fewer than {} items", path, schema["minItems"]));
+                }
+                if schema["maxItems"].as_u64().is_some_and(|max| len > max) {
+                    errors.push(format!("{}: more than {} items", path, schema["maxItems"]));
+                }
+                if let Some(items_schema) = schema.get("items") {
+                    for (i, item) in items.iter().enumerate() {
+                        let path = format!("{}[{}]", path, i);
+                        self.validate(items_schema, item, &path, errors, depth + 1);
+                    }
+                }
+            }
+            Value::Object(map) => {
+                for key in schema["required"].as_array().into_iter().flatten() {
+                    let key = key.as_str().unwrap_or_default();
+                    if !map.contains_key(key) {
+                        errors.push(format!("{}.{}: required property is missing", path, key));
+                    }
+                }
+                let properties = schema["properties"].as_object();
+                for (key, v) in map {
+                    let path = format!("{}.{}", path, key);
+                    match (
+                        properties.and_then(|p| p.get(key)),
+                        &schema["additionalProperties"],
+                    ) {
+                        (Some(s), _) => self.validate(s, v, &path, errors, depth + 1),
+                        (None, Value::Bool(false)) => {
+                            errors.push(format!("{}: additional property is not allowed", path))
+                        }
+                        (None, s @ Value::Object(_)) => {
+                            self.validate(s, v, &path, errors, depth + 1)
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // 只支持文档内部的引用，比如 `#/components/schemas/Todo`
+    fn resolve<'a>(&'a self, mut value: &'a Value) -> &'a Value {
+        for _ in 0..MAX_DEPTH {
+            match value["$ref"].as_str().and_then(|r| r.strip_prefix('#')) {
+                Some(pointer) => match self.0.pointer(pointer) {
+                    Some(target) => value = target,
+                    None => return &Value::Null,
+                },
+                None => return value,
+            }
+        }
+        value
+    }
+}
+
+impl FromStr for OpenApi {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        // yaml 是 json 的超集，两种格式都可以直接解析
+        let doc: Value = serde_yaml::from_str(s)?;
+        match doc["openapi"].as_str() {
+            Some(version) if version.starts_with('3') => Ok(Self(doc)),
+            _ => Err(anyhow!("only OpenAPI 3 documents are supported")),
+        }
+    }
+}
+
+impl Operation<'_> {
+    fn id(&self) -> String {
+        format!("{} {}", self.method.to_uppercase(), self.path)
+    }
+}
+
+impl OpenApiAssert {
+    /// validate the response body, failures of loading the document are reported as well
+    pub fn check(&self, status: u16, body: &Value) -> Vec<String> {
+        OpenApi::load_cached(&self.file)
+            .and_then(|doc| doc.check(&self.operation, status, body))
+            .unwrap_or_else(|e| vec![format!("openapi {}: {:#}", self.operation, e)])
+    }
+}
+
+impl ConfigValidate for OpenApiAssert {
+    fn validate(&self) -> Result<()> {
+        OpenApi::load_cached(&self.file)?
+            .operation(&self.operation)
+            .map(|_| ())
+            .ok_or_else(|| anyhow!("operation {} not found", self.operation))
+    }
+}
+
+// `type` 在 3.0 中是字符串，在 3.1 中可以是数组
+fn schema_types(schema: &Value) -> Vec<&str> {
+    match &schema["type"] {
+        Value::String(t) => vec![t.as_str()],
+        Value::Array(types) => types.iter().filter_map(|t| t.as_str()).collect(),
+        _ => vec![],
+    }
+}
+
+// getTodoById -> get_todo_by_id
+fn snake_case(id: &str) -> String {
+    let mut name = String::new();
+    let mut prev = '_';
+    for c in id.chars() {
+        let c = if c.is_ascii_alphanumeric() { c } else { '_' };
+        if c.is_ascii_uppercase() && (prev.is_ascii_lowercase() || prev.is_ascii_digit()) {
+            name.push('_');
+        }
+        if c != '_' || !name.ends_with('_') {
+            name.push(c.to_ascii_lowercase());
+        }
+        prev = c;
+    }
+    name.trim_matches('_').to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ConfigLoad;
+
+    const DOC: &str = r##"
+openapi: 3.0.3
+servers:
+  - url: "{scheme}://example.com/v1"
+    variables:
+      scheme:
+        default: https
+paths:
+  /todos:
+    post:
+      requestBody:
+        content:
+          application/json:
+            schema:
+              type: array
+              items:
+                $ref: "#/components/schemas/Todo"
+      responses:
+        default:
+          description: ok
+  /todos/{id}:
+    parameters:
+      - name: id
+        in: path
+        required: true
+        schema:
+          type: integer
+          example: 42
+    get:
+      operationId: getTodoById
+      parameters:
+        - name: fields
+          in: query
+          example: title
+      responses:
+        "200":
+          content:
+            application/json:
+              schema:
+                $ref: "#/components/schemas/Todo"
+    put:
+      requestBody:
+        content:
+          application/json:
+            schema:
+              $ref: "#/components/schemas/Todo"
+      responses:
+        default:
+          description: ok
+  /files/{name}:
+    get:
+      parameters:
+        - name: name
+          in: path
+          required: true
+          example: a b/c.txt
+      responses:
+        "200":
+          content:
+            application/json:
+              schema:
+                oneOf:
+                  - type: integer
+                  - type: number
+components:
+  schemas:
+    Todo:
+      type: object
+      required: [id, title]
+      properties:
+        id:
+          type: integer
+        title:
+          type: string
+          minLength: 1
+        tags:
+          type: array
+          items:
+            type: string
+        due:
+          type: string
+          format: date
+          nullable: true
+"##;
+
+    #[test]
+    fn openapi_should_generate_profiles_and_validate_responses() {
+        let doc: OpenApi = DOC.parse().unwrap();
+        let config = doc
+            .to_request_config(Path::new("openapi.yml"), None)
+            .unwrap();
+        let profile = &config.profiles["get_todo_by_id"];
+        assert_eq!(profile.url.as_str(), "https://example.com/v1/todos/42");
+        assert_eq!(profile.params, Some(json!({"fields": "title"})));
+        let openapi = profile.assert.as_ref().unwrap().openapi.as_ref().unwrap();
+        assert_eq!(openapi.operation, "getTodoById");
+        let profile = &config.profiles["put_todos_id"];
+        assert_eq!(
+            profile.body,
+            Some(json!({"id": 1, "title": "string", "tags": ["string"], "due": "2024-01-01"}))
+        );
+
+        let body = json!({"id": 1, "title": "todo", "due": null});
+        assert!(doc.check("GET /todos/{id}", 200, &body).unwrap().is_empty());
+        let body = json!({"id": "1", "title": "", "tags": ["a", 2]});
+        assert_eq!(
+            doc.check("getTodoById", 200, &body).unwrap(),
+            vec![
+                "$.id: expected type integer, got string",
+                "$.title: shorter than 1",
+                "$.tags[1]: expected type string, got integer",
+            ]
+        );
+        assert!(doc.check("getTodoById", 404, &body).is_err());
+
+        // path 参数需要编码，oneOf 只能匹配一个
+        let profile = &config.profiles["get_files_name"];
+        assert_eq!(
+            profile.url.as_str(),
+            "https://example.com/v1/files/a%20b%2Fc.txt"
+        );
+        assert!(doc
+            .check("GET /files/{name}", 200, &json!(1.5))
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            doc.check("GET /files/{name}", 200, &json!(1)).unwrap(),
+            vec!["$: matches 2 schemas of oneOf, expected exactly one"]
+        );
+    }
+
+    #[test]
+    fn openapi_config_should_load_again() {
+        let (_dir, file) = crate::util::temp_file("openapi.yml", DOC);
+        let doc = OpenApi::load(&file).unwrap();
+        let config = doc.to_request_config(&file, None).unwrap();
+        let yaml = serde_yaml::to_string(&config).unwrap();
+        let config = RequestConfig::from_yaml(&yaml).unwrap();
+        assert_eq!(
+            config.profiles["post_todos"].body,
+            Some(json!([{"id": 1, "title": "string", "tags": ["string"], "due": "2024-01-01"}]))
+        );
+    }
+
+    #[test]
+    fn openapi_assert_file_should_be_relative_to_config_dir() {
+        let (dir, file) = crate::util::temp_file("openapi.yml", DOC);
+        let doc = OpenApi::load(&file).unwrap();
+        // import 时写入的是相对于输出文件的路径
+        let config = doc
+            .to_request_config(Path::new("openapi.yml"), None)
+            .unwrap();
+        let yaml = serde_yaml::to_string(&config).unwrap();
+        let config =
+            RequestConfig::from_yaml_in_dir(&yaml, &Default::default(), dir.path()).unwrap();
+        let assert = config.profiles["post_todos"].assert.as_ref().unwrap();
+        assert_eq!(assert.openapi.as_ref().unwrap().file, file);
+        assert!(RequestConfig::from_yaml(&yaml).is_err());
+    }
+
+    #[test]
+    fn openapi_example_should_stop_at_recursive_refs() {
+        let doc: OpenApi = r##"
+openapi: 3.0.3
+paths:
+  /nodes:
+    post:
+      requestBody:
+        content:
+          application/json:
+            schema:
+              $ref: "#/components/schemas/Node"
+      responses:
+        default:
+          description: ok
+components:
+  schemas:
+    Node:
+      type: object
+      properties:
+        value:
+          type: integer
+        left:
+          $ref: "#/components/schemas/Node"
+        right:
+          $ref: "#/components/schemas/Node"
+        children:
+          type: array
+          items:
+            $ref: "#/components/schemas/Node"
+"##
+        .parse()
+        .unwrap();
+        let config = doc
+            .to_request_config(
+                Path::new("openapi.yml"),
+                Some(&"http://localhost".parse().unwrap()),
+            )
+            .unwrap();
+        assert_eq!(
+            config.profiles["post_nodes"].body,
+            Some(json!({"value": 1, "children": []}))
+        );
+    }
+
+    #[test]
+    fn openapi_document_should_be_cached() {
+        let (_dir, file) = crate::util::temp_file("openapi.yml", DOC);
+        let doc = OpenApi::load_cached(&file).unwrap();
+        assert!(Arc::ptr_eq(&doc, &OpenApi::load_cached(&file).unwrap()));
+        let assert = OpenApiAssert {
+            file,
+            operation: "getTodoById".into(),
+        };
+        assert.validate().unwrap();
+        assert!(assert
+            .check(200, &json!({"id": 1, "title": "a"}))
+            .is_empty());
+    }
+}
@@ -0,0 +1,228 @@
+use std::{path::PathBuf, sync::OnceLock};
+
+use anyhow::{anyhow, Context, Result};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use prost::Message;
+use prost_reflect::{DescriptorPool, DynamicMessage, MessageDescriptor, SerializeOptions};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{is_default, ConfigValidate};
+
+// profile 中的 protobuf 配置，body 按 request 的 message 类型编码，响应按 response 的 message 类型解码为 json
+// ```yaml
+// proto:
+//   file: ./protos/todo.proto
+//   request: todo.CreateTodoRequest
+//   response: todo.Todo
+//   base64: true
+// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProtoConfig {
+    /// the .proto file
+    pub file: PathBuf,
+    /// import paths of the .proto file, default to the dir of the file
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub includes: Vec<PathBuf>,
+    /// full name of the request message, e.g. `todo.CreateTodoRequest`
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub request: Option<String>,
+    /// full name of the response message
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub response: Option<String>,
+    /// request and response bodies are base64 encoded protobuf bytes
+    #[serde(skip_serializing_if = "is_default", default)]
+    pub base64: bool,
+    // 编译后的 .proto，每个 profile 只编译一次
+    #[serde(skip)]
+    pool: PoolCache,
+}
+
+#[derive(Debug, Clone, Default)]
+struct PoolCache(OnceLock<DescriptorPool>);
+
+// 缓存不影响配置是否相同
+impl PartialEq for PoolCache {
+    fn eq(&self, _: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for PoolCache {}
+
+// 响应的 content-type 是 protobuf 时才按 response message 解码，其他的（比如 json 的错误信息）正常解码
+const PROTO_CONTENT_TYPES: &[&str] = &[
+    "application/x-protobuf",
+    "application/protobuf",
+    "application/vnd.google.protobuf",
+    "application/octet-stream",
+    "ad-bill-pb/base64",
+];
+
+/// whether the value of the content-type header is a protobuf type
+pub fn is_protobuf(content_type: Option<&str>) -> bool {
+    content_type
+        .and_then(|ct| ct.split(';').next())
+        .is_some_and(|mime| {
+            PROTO_CONTENT_TYPES
+                .iter()
+                .any(|t| t.eq_ignore_ascii_case(mime.trim()))
+        })
+}
+
+impl ProtoConfig {
+    /// resolve the relative .proto file and import paths against the dir of the config file
+    pub fn resolve_paths(&mut self, dir: &std::path::Path) {
+        self.file = dir.join(&self.file);
+        for include in &mut self.includes {
+            *include = dir.join(&*include);
+        }
+    }
+
+    fn pool(&self) -> Result<&DescriptorPool> {
+        if let Some(pool) = self.pool.0.get() {
+            return Ok(pool);
+        }
+        let pool = self.compile()?;
+        Ok(self.pool.0.get_or_init(|| pool))
+    }
+
+    fn compile(&self) -> Result<DescriptorPool> {
+        let includes = if self.includes.is_empty() {
+            let dir = self.file.parent().map(PathBuf::from).unwrap_or_default();
+            vec![if dir.as_os_str().is_empty() {
+                PathBuf::from(".")
+            } else {
+                dir
+            }]
+        } else {
+            self.includes.clone()
+        };
+        let mut compiler = protox::Compiler::new(includes)?;
+        compiler
+            .include_imports(true)
+            .open_file(&self.file)
+            .with_context(|| format!("compile proto file: {}", self.file.display()))?;
+        Ok(compiler.descriptor_pool())
+    }
+
+    fn message(pool: &DescriptorPool, name: &str) -> Result<MessageDescriptor> {
+        pool.get_message_by_name(name)
+            .ok_or_else(|| anyhow!("Proto message: {} not found", name))
+    }
+
+    /// encode the json body to the request message
+    pub fn encode(&self, body: &Value) -> Result<Option<Vec<u8>>> {
+        let name = match &self.request {
+            Some(name) => name,
+            None => return Ok(None),
+        };
+        let desc = Self::message(self.pool()?, name)?;
+        let msg = DynamicMessage::deserialize(desc, body.clone())
+            .with_context(|| format!("encode body as proto message: {}", name))?;
+        let data = msg.encode_to_vec();
+        Ok(Some(if self.base64 {
+            STANDARD.encode(data).into_bytes()
+        } else {
+            data
+        }))
+    }
+
+    /// decode the response bytes to json by the response message, `None` if the response
+    /// message is not set or the content type is not protobuf
+    pub fn decode(&self, content_type: Option<&str>, data: &[u8]) -> Result<Option<Value>> {
+        let name = match &self.response {
+            Some(name) if is_protobuf(content_type) => name,
+            _ => return Ok(None),
+        };
+        let desc = Self::message(self.pool()?, name)?;
+        let data = if self.base64 {
+            let text = std::str::from_utf8(data).context("decode base64 proto response")?;
+            STANDARD.decode(text.trim())?
+        } else {
+            data.to_vec()
+        };
+        let msg = DynamicMessage::decode(desc, data.as_slice())
+            .with_context(|| format!("decode response as proto message: {}", name))?;
+        // 使用 .proto 中定义的字段名，和 body 的写法保持一致
+        let options = SerializeOptions::new().use_proto_field_name(true);
+        Ok(Some(msg.serialize_with_options(
+            serde_json::value::Serializer,
+            &options,
+        )?))
+    }
+}
+
+impl ConfigValidate for ProtoConfig {
+    fn validate(&self) -> Result<()> {
+        let pool = self.pool()?;
+        for name in [&self.request, &self.response].into_iter().flatten() {
+            Self::message(pool, name)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::temp_file;
+    use serde_json::json;
+
+    #[test]
+    fn proto_encode_decode_should_work() {
+        let (_dir, file) = temp_file(
+            "todo.proto",
+            "syntax = \"proto3\";\npackage todo;\nmessage Todo {\n  int32 id = 1;\n  string title = 2;\n  repeated string tags = 3;\n}\n",
+        );
+        let proto = ProtoConfig {
+            file,
+            request: Some("todo.Todo".into()),
+            response: Some("todo.Todo".into()),
+            base64: true,
+            ..Default::default()
+        };
+        proto.validate().unwrap();
+        let body = json!({"id": 1, "title": "proto", "tags": ["a"]});
+        let data = proto.encode(&body).unwrap().unwrap();
+        assert_eq!(String::from_utf8(data.clone()).unwrap(), "CAESBXByb3RvGgFh");
+        let content_type = Some("application/x-protobuf");
+        assert_eq!(proto.decode(content_type, &data).unwrap(), Some(body));
+        // 错误响应不按 protobuf 解码
+        let error = br#"{"error":"not found"}"#;
+        assert_eq!(proto.decode(Some("application/json"), error).unwrap(), None);
+
+        let proto = ProtoConfig {
+            response: Some("todo.NotExists".into()),
+            ..proto
+        };
+        assert!(proto.validate().is_err());
+    }
+
+    #[test]
+    fn proto_file_should_be_relative_to_config_dir() {
+        use crate::{ConfigLoad, RequestConfig};
+
+        let (dir, file) = temp_file(
+            "todo.proto",
+            "syntax = \"proto3\";\npackage todo;\nmessage Todo {\n  int32 id = 1;\n}\n",
+        );
+        let content = r#"
+todo:
+  method: POST
+  url: https://example.com/todos
+  body:
+    id: 1
+  proto:
+    file: todo.proto
+    includes: ["."]
+    request: todo.Todo
+"#;
+        let config =
+            RequestConfig::from_yaml_in_dir(content, &Default::default(), dir.path()).unwrap();
+        let proto = config.profiles["todo"].proto.as_ref().unwrap();
+        assert_eq!(proto.file, file);
+        assert_eq!(proto.includes, vec![dir.path().join(".")]);
+        assert!(RequestConfig::from_yaml(content).is_err());
+    }
+}
@@ -0,0 +1,136 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+
+use super::{filter_body, xdiff::ResponseProfile, FilteredResponse};
+
+// 记录到文件中的响应快照（yaml 格式），用于和线上的响应做 diff
+// 快照中保存的是没有过滤的响应，加载时按当前的 ResponseProfile 过滤，修改 skip_headers 和 skip_body 后不需要重新录制
+
+/// load the snapshot and apply the skip rules of the response profile
+pub fn load(path: impl AsRef<Path>, res: &ResponseProfile) -> Result<FilteredResponse> {
+    let path = path.as_ref();
+    if !path.is_file() {
+        return Err(anyhow!(
+            "snapshot {} not found, run with --update to record it",
+            path.display()
+        ));
+    }
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("read snapshot: {}", path.display()))?;
+    let snapshot: FilteredResponse = serde_yaml::from_str(&content)
+        .with_context(|| format!("parse snapshot: {}", path.display()))?;
+    filter(snapshot, res)
+}
+
+/// apply the skip rules of the response profile to the unfiltered response
+pub fn filter(mut snapshot: FilteredResponse, res: &ResponseProfile) -> Result<FilteredResponse> {
+    snapshot
+        .headers
+        .retain(|k, _| !res.skip_headers.contains(k));
+    snapshot.body = filter_body(snapshot.body, &res.skip_body)?;
+    Ok(snapshot)
+}
+
+/// write the unfiltered response to the snapshot file
+pub fn save(path: impl AsRef<Path>, snapshot: &FilteredResponse) -> Result<()> {
+    let path = path.as_ref();
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(path, serde_yaml::to_string(snapshot)?)
+        .with_context(|| format!("write snapshot: {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::{xdiff::DiffConfig, ConfigLoad, GetProfile},
+        ExtraArgs,
+    };
+    use reqwest::Client;
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    #[test]
+    fn snapshot_should_be_filtered_on_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("snapshots/todo.yml");
+        let res = ResponseProfile::new(vec!["date".into()], vec!["updated_at".into()]);
+        assert!(load(&path, &res).is_err());
+
+        let snapshot = FilteredResponse {
+            status: "HTTP/1.1 200 OK".into(),
+            headers: [
+                ("content-type".to_string(), "application/json".to_string()),
+                (
+                    "date".to_string(),
+                    "Sun, 18 Oct 2026 08:00:00 GMT".to_string(),
+                ),
+            ]
+            .into_iter()
+            .collect(),
+            body: json!({"id": 1, "updated_at": 2}),
+        };
+        save(&path, &snapshot).unwrap();
+        let loaded = load(&path, &res).unwrap();
+        assert_eq!(loaded.headers.len(), 1);
+        assert_eq!(loaded.body, json!({"id": 1}));
+    }
+
+    #[tokio::test]
+    async fn snapshot_should_record_unfiltered_response() {
+        let _m = mockito::mock("GET", "/snapshot/todo")
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"id": 1, "updated_at": 2}"#)
+            .create();
+        let dir = tempfile::tempdir().unwrap();
+        let yaml = format!(
+            r#"
+todo:
+  req1:
+    url: {}/snapshot/todo
+  snapshot: snapshots/todo.yml
+  res:
+    skip_body: [updated_at]
+"#,
+            mockito::server_url()
+        );
+        let config = DiffConfig::from_yaml_in_dir(&yaml, &HashMap::new(), dir.path()).unwrap();
+        let profile = config.get_profile("todo").unwrap();
+        // 相对于配置文件所在的目录
+        let path = dir.path().join("snapshots/todo.yml");
+        assert_eq!(profile.snapshot.as_deref(), path.to_str());
+
+        let args = ExtraArgs::default();
+        let result = profile
+            .diff(&Client::new(), args.clone(), true)
+            .await
+            .unwrap();
+        assert_eq!(result.res1.body, json!({"id": 1}));
+        assert!(result.is_same());
+        let saved: FilteredResponse =
+            serde_yaml::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(saved.body, json!({"id": 1, "updated_at": 2}));
+        assert!(profile
+            .diff(&Client::new(), args.clone(), false)
+            .await
+            .unwrap()
+            .is_same());
+
+        // 再次更新时和之前的快照比较
+        drop(_m);
+        let _m = mockito::mock("GET", "/snapshot/todo")
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"id": 2, "updated_at": 3}"#)
+            .create();
+        let result = profile.diff(&Client::new(), args, true).await.unwrap();
+        assert_eq!(result.res1.body, json!({"id": 1}));
+        assert_eq!(result.res2.body, json!({"id": 2}));
+        assert!(!result.is_same());
+        let saved: FilteredResponse =
+            serde_yaml::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(saved.body, json!({"id": 2, "updated_at": 3}));
+    }
+}
@@ -1,18 +1,43 @@
-use crate::{util::text_diff, ExtraArgs};
-use std::collections::HashMap;
+use crate::{
+    jsonpath::JsonPath,
+    util::{glob_match, json_changes_text, json_diff, text_diff, unified_diff, JsonChange},
+    ExtraArgs,
+};
+use std::{
+    collections::HashMap,
+    path::Path,
+    time::{Duration, Instant},
+};
 
-use super::{is_default, ConfigLoad, ConfigValidate, GetProfile, RequestProfile};
+use super::{
+    client::ClientConfig, is_default, mirror::MirrorConfig, oauth2::OAuth2Config, snapshot,
+    ConfigLoad, ConfigValidate, FilteredResponse, GetProfile, RequestProfile,
+};
 
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
 
+use clap::ValueEnum;
+use reqwest::Client;
 use serde::{Deserialize, Serialize};
 
+// diff 的方式，text 为按行 diff，json 为按 json 结构 diff
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Default, PartialEq, Eq, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum DiffMode {
+    #[default]
+    Text,
+    Json,
+}
+
 #[derive(Debug, Serialize, Deserialize, Default, PartialEq, Eq)]
 pub struct ResponseProfile {
     #[serde(skip_serializing_if = "Vec::is_empty", default)]
     pub skip_headers: Vec<String>,
+    // 支持 json path 写法，比如：`data.items[*].updated_at`, `$..trace_id`
     #[serde(skip_serializing_if = "Vec::is_empty", default)]
     pub skip_body: Vec<String>,
+    #[serde(skip_serializing_if = "is_default", default)]
+    pub mode: DiffMode,
 }
 
 impl ResponseProfile {
@@ -20,12 +45,22 @@ impl ResponseProfile {
         Self {
             skip_headers,
             skip_body,
+            mode: DiffMode::default(),
         }
     }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct DiffConfig {
+    // 所有 profile 共用的 http client 配置，`client` 不能作为 profile 的名字
+    #[serde(skip_serializing_if = "is_default", default)]
+    pub client: ClientConfig,
+    // 所有 profile 共用的 oauth2 token，`oauth2` 不能作为 profile 的名字
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub oauth2: Option<OAuth2Config>,
+    // `xdiff mirror` 的配置，`mirror` 不能作为 profile 的名字
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub mirror: Option<MirrorConfig>,
     #[serde(flatten)]
     pub profiles: HashMap<String, DiffProfile>,
 }
@@ -34,14 +69,43 @@ pub struct DiffConfig {
 pub struct DiffProfile {
     // 请求相关的profile 配置
     pub req1: RequestProfile,
-    pub req2: RequestProfile,
+    // 和 snapshot 二选一
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub req2: Option<RequestProfile>,
+    // 响应快照文件，设置时 req1 的响应和快照做 diff，`xdiff run --update` 录制新的快照
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub snapshot: Option<String>,
     // 响应中有需要skip 的阈，
     #[serde(skip_serializing_if = "is_default", default)]
     pub res: ResponseProfile,
+    // 有些接口需要按顺序调用，设置为 true 时 req1 和 req2 依次发送
+    #[serde(skip_serializing_if = "is_default", default)]
+    pub sequential: bool,
+    // 用于 `xdiff run --tag` 选择一组 profile
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub tags: Vec<String>,
 }
 
-// 直接使用公共的 config load trait 的实现，不需要重复写
-impl ConfigLoad for DiffConfig {}
+impl ConfigLoad for DiffConfig {
+    const RESERVED_KEYS: &'static [&'static str] = &["client", "oauth2", "mirror"];
+
+    // 快照等文件的相对路径相对于配置文件所在的目录
+    fn resolve_paths(&mut self, dir: &Path) {
+        self.client.resolve_paths(dir);
+        if let Some(oauth2) = &mut self.oauth2 {
+            oauth2.resolve_paths(dir);
+        }
+        for profile in self.profiles.values_mut() {
+            profile.req1.resolve_paths(dir);
+            if let Some(req2) = &mut profile.req2 {
+                req2.resolve_paths(dir);
+            }
+            if let Some(path) = &mut profile.snapshot {
+                *path = dir.join(&*path).to_string_lossy().into_owned();
+            }
+        }
+    }
+}
 
 impl GetProfile for DiffConfig {
     // 关联类型为 DiffProfile
@@ -53,36 +117,206 @@ impl GetProfile for DiffConfig {
 
 impl DiffConfig {
     pub fn new(profiles: HashMap<String, DiffProfile>) -> Self {
-        Self { profiles }
+        Self {
+            client: ClientConfig::default(),
+            oauth2: None,
+            mirror: None,
+            profiles,
+        }
+    }
+
+    /// select profiles by name (or glob pattern) and tags, sorted by name
+    pub fn select_profiles(
+        &self,
+        pattern: Option<&str>,
+        tags: &[String],
+    ) -> Vec<(&String, &DiffProfile)> {
+        let mut profiles: Vec<_> = self
+            .profiles
+            .iter()
+            .filter(|(name, _)| pattern.is_none_or(|p| glob_match(p, name)))
+            .filter(|(_, profile)| tags.iter().all(|t| profile.tags.contains(t)))
+            .collect();
+        profiles.sort_by(|a, b| a.0.cmp(b.0));
+        profiles
     }
 }
 
 impl DiffProfile {
     pub fn new(req1: RequestProfile, req2: RequestProfile, res: ResponseProfile) -> Self {
-        Self { req1, req2, res }
+        Self {
+            req1,
+            req2: Some(req2),
+            snapshot: None,
+            res,
+            sequential: false,
+            tags: vec![],
+        }
     }
-    pub async fn diff(&self, args: ExtraArgs) -> Result<String> {
+    /// diff the responses of req1 and req2 (or the snapshot), `update` records the snapshot
+    pub async fn diff(&self, client: &Client, args: ExtraArgs, update: bool) -> Result<DiffResult> {
+        if let Some(path) = &self.snapshot {
+            return self.diff_snapshot(client, path, &args, update).await;
+        }
+        let req2 = self
+            .req2
+            .as_ref()
+            .ok_or_else(|| anyhow!("req2 or snapshot is required"))?;
         // _args 是需要override 的参数（由用户通过命令行传入）
         // 从命令行拿到的参数，先合并到对应的：req，res
         // 然后 send request 得到具体的，响应内容
-        let res1 = self.req1.send(&args).await?;
-        let res2 = self.req2.send(&args).await?;
-        // // 从响应内容中去除掉需要skip 的text，剩下需要进行 diff 比较的text
-        let text1 = res1.filter_text(&self.res).await?;
-        let text2 = res2.filter_text(&self.res).await?;
-        // println!("{}", text1);
-        // println!("text2: {}", text2);
+        // 默认两个请求并发发送，避免两次请求之间的数据发生变化
+        let fut1 = self.send(client, &self.req1, &args, &self.res);
+        let fut2 = self.send(client, req2, &args, &self.res);
+        let ((res1, elapsed1), (res2, elapsed2)) = if self.sequential {
+            (fut1.await?, fut2.await?)
+        } else {
+            tokio::try_join!(fut1, fut2)?
+        };
+
+        Ok(DiffResult {
+            mode: self.res.mode,
+            res1,
+            res2,
+            elapsed1,
+            elapsed2,
+            snapshot: false,
+        })
+    }
+
+    // 快照作为 res1，req1 的实时响应作为 res2，更新快照时两者相同
+    // 快照记录没有过滤的响应，两者都按当前的 ResponseProfile 过滤
+    async fn diff_snapshot(
+        &self,
+        client: &Client,
+        path: &str,
+        args: &ExtraArgs,
+        update: bool,
+    ) -> Result<DiffResult> {
+        let (raw, elapsed) = self
+            .send(client, &self.req1, args, &ResponseProfile::default())
+            .await?;
+        // 更新时和之前的快照比较，显示这次更新的变化，还没有快照时和新的快照相同
+        let saved = if update && !Path::new(path).exists() {
+            snapshot::filter(raw.clone(), &self.res)?
+        } else {
+            snapshot::load(path, &self.res)?
+        };
+        if update {
+            snapshot::save(path, &raw)?;
+        }
+        let res = snapshot::filter(raw, &self.res)?;
+        Ok(DiffResult {
+            mode: self.res.mode,
+            res1: saved,
+            res2: res,
+            elapsed1: Duration::ZERO,
+            elapsed2: elapsed,
+            snapshot: true,
+        })
+    }
+
+    // 发送请求，并从响应内容中去除掉需要skip 的内容，同时记录请求的耗时
+    async fn send(
+        &self,
+        client: &Client,
+        req: &RequestProfile,
+        args: &ExtraArgs,
+        res: &ResponseProfile,
+    ) -> Result<(FilteredResponse, Duration)> {
+        let start = Instant::now();
+        let res = req.send(client, args).await?.filter(res).await?;
+        Ok((res, start.elapsed()))
+    }
+}
+
+// 两个请求经过过滤之后的响应，根据需要输出不同格式的 diff
+#[derive(Debug, Clone)]
+pub struct DiffResult {
+    pub mode: DiffMode,
+    pub res1: FilteredResponse,
+    pub res2: FilteredResponse,
+    pub elapsed1: Duration,
+    pub elapsed2: Duration,
+    // res1 是快照，没有发送请求
+    pub snapshot: bool,
+}
+
+impl DiffResult {
+    /// json mode ignores the order of array elements, same as the changes
+    pub fn is_same(&self) -> bool {
+        match self.mode {
+            DiffMode::Text => self.res1 == self.res2,
+            DiffMode::Json => self.changes().is_ok_and(|c| c.is_empty()),
+        }
+    }
+
+    /// structural changes of status, headers and body
+    pub fn changes(&self) -> Result<DiffChanges> {
+        Ok(DiffChanges {
+            status: json_diff(
+                "status",
+                &self.res1.status.clone().into(),
+                &self.res2.status.clone().into(),
+            ),
+            headers: json_diff(
+                "headers",
+                &serde_json::to_value(&self.res1.headers)?,
+                &serde_json::to_value(&self.res2.headers)?,
+            ),
+            body: json_diff("$", &self.res1.body, &self.res2.body),
+        })
+    }
+
+    /// colored diff text for terminal, based on the diff mode
+    pub fn text(&self) -> Result<String> {
+        match self.mode {
+            DiffMode::Text => text_diff(&self.res1.to_text()?, &self.res2.to_text()?),
+            DiffMode::Json => {
+                let changes = self.changes()?;
+                let all: Vec<_> = [changes.status, changes.headers, changes.body].concat();
+                Ok(json_changes_text(&all))
+            }
+        }
+    }
 
-        // // 调用 similar 的 string 的diff 函数进行输出。
-        let output = text_diff(&text1, &text2)?;
-        // println!("profile: {:?}", &self);
-        // println!("extra_prams: {:?}", &args);
-        // println!("{}", output);
+    /// unified diff without any escape codes
+    pub fn patch(&self, name: &str) -> Result<String> {
+        let (label1, label2) = if self.snapshot {
+            ("snapshot", "req1")
+        } else {
+            ("req1", "req2")
+        };
+        Ok(unified_diff(
+            &self.res1.to_text()?,
+            &self.res2.to_text()?,
+            &format!("{}/{}", name, label1),
+            &format!("{}/{}", name, label2),
+        ))
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DiffChanges {
+    pub status: Vec<JsonChange>,
+    pub headers: Vec<JsonChange>,
+    pub body: Vec<JsonChange>,
+}
 
-        Ok(output)
-        // 错误处理：针对，程序范围的无效的输入，配置等作出更好的出错的提示。
+impl DiffChanges {
+    pub fn is_empty(&self) -> bool {
+        self.status.is_empty() && self.headers.is_empty() && self.body.is_empty()
+    }
+}
 
-        // todo!()
+// validate for response profile
+impl ConfigValidate for ResponseProfile {
+    fn validate(&self) -> Result<()> {
+        for path in &self.skip_body {
+            path.parse::<JsonPath>()
+                .with_context(|| format!("skip_body: {}", path))?;
+        }
+        Ok(())
     }
 }
 
@@ -90,7 +324,15 @@ impl DiffProfile {
 impl ConfigValidate for DiffProfile {
     fn validate(&self) -> Result<()> {
         self.req1.validate().context("req1 config error")?;
-        self.req2.validate().context("req2 config error")?;
+        match (&self.req2, &self.snapshot) {
+            (Some(req2), None) => req2.validate().context("req2 config error")?,
+            (None, Some(path)) if Path::new(path).is_dir() => {
+                return Err(anyhow!("snapshot: {} is a directory", path));
+            }
+            (None, Some(_)) => {}
+            _ => return Err(anyhow!("exactly one of req2 and snapshot is required")),
+        }
+        self.res.validate().context("res config error")?;
         Ok(())
     }
 }
@@ -98,6 +340,13 @@ impl ConfigValidate for DiffProfile {
 // validate
 impl ConfigValidate for DiffConfig {
     fn validate(&self) -> Result<()> {
+        self.client.validate().context("client config error")?;
+        if let Some(oauth2) = &self.oauth2 {
+            oauth2.validate().context("oauth2 config error")?;
+        }
+        if let Some(mirror) = &self.mirror {
+            mirror.validate().context("mirror config error")?;
+        }
         for (name, profile) in &self.profiles {
             profile
                 .validate()
@@ -1,26 +1,67 @@
-use super::{ConfigLoad, ConfigValidate, GetProfile, RequestProfile};
-use std::collections::HashMap;
+use super::{
+    client::ClientConfig, flow::Flow, is_default, oauth2::OAuth2Config, ConfigLoad, ConfigValidate,
+    GetProfile, RequestProfile,
+};
+use crate::util::glob_match;
+use std::{collections::HashMap, path::Path};
 
-use anyhow::{Context, Result};
+use anyhow::{anyhow, Context, Result};
 
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct RequestConfig {
+    // 所有 profile 共用的 http client 配置，`client` 不能作为 profile 的名字
+    #[serde(skip_serializing_if = "is_default", default)]
+    pub client: ClientConfig,
+    // 按顺序执行多个 profile 的 flow，`flows` 不能作为 profile 的名字
+    #[serde(skip_serializing_if = "HashMap::is_empty", default)]
+    pub flows: HashMap<String, Flow>,
+    // 所有 profile 共用的 oauth2 token，`oauth2` 不能作为 profile 的名字
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub oauth2: Option<OAuth2Config>,
     #[serde(flatten)]
     pub profiles: HashMap<String, RequestProfile>,
 }
 
-// 直接使用公共的 config load trait 的实现，不需要重复写
-impl ConfigLoad for RequestConfig {}
+impl ConfigLoad for RequestConfig {
+    const RESERVED_KEYS: &'static [&'static str] = &["client", "flows", "oauth2"];
+
+    // 文件的相对路径相对于配置文件所在的目录
+    fn resolve_paths(&mut self, dir: &Path) {
+        self.client.resolve_paths(dir);
+        if let Some(oauth2) = &mut self.oauth2 {
+            oauth2.resolve_paths(dir);
+        }
+        for profile in self.profiles.values_mut() {
+            profile.resolve_paths(dir);
+        }
+    }
+}
 
 impl ConfigValidate for RequestConfig {
     fn validate(&self) -> Result<()> {
+        self.client.validate().context("client config error")?;
+        if let Some(oauth2) = &self.oauth2 {
+            oauth2.validate().context("oauth2 config error")?;
+        }
         for (name, profile) in &self.profiles {
             profile
                 .validate()
                 .with_context(|| format!("profile: {}", name))?;
         }
+        for (name, flow) in &self.flows {
+            flow.validate().with_context(|| format!("flow: {}", name))?;
+            for step in &flow.steps {
+                if !self.profiles.contains_key(&step.profile) {
+                    return Err(anyhow!(
+                        "flow: {}: profile {} not found",
+                        name,
+                        step.profile
+                    ));
+                }
+            }
+        }
         Ok(())
     }
 }
@@ -35,6 +76,22 @@ impl GetProfile for RequestConfig {
 
 impl RequestConfig {
     pub fn new(profiles: HashMap<String, RequestProfile>) -> Self {
-        Self { profiles }
+        Self {
+            client: ClientConfig::default(),
+            flows: HashMap::new(),
+            oauth2: None,
+            profiles,
+        }
+    }
+
+    /// profiles matching the glob pattern (all if none) sorted by name
+    pub fn select_profiles(&self, pattern: Option<&str>) -> Vec<(&String, &RequestProfile)> {
+        let mut profiles: Vec<_> = self
+            .profiles
+            .iter()
+            .filter(|(name, _)| pattern.is_none_or(|p| glob_match(p, name)))
+            .collect();
+        profiles.sort_by(|a, b| a.0.cmp(b.0));
+        profiles
     }
 }
@@ -0,0 +1,297 @@
+use std::{fmt, str::FromStr};
+
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+
+// 一个简化版的 JSONPath 实现，支持：
+// - `$.data.items` / `data.items` 点号路径（`$` 可以省略）
+// - `items[0]` / `items[*]` / `['a.b']` 数组下标，通配符，以及带引号的 key
+// - `..trace_id` 递归下降
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsonPath {
+    raw: String,
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Child(Selector),
+    Descendant(Selector),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Selector {
+    Key(String),
+    Index(usize),
+    Wildcard,
+}
+
+impl FromStr for JsonPath {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let raw = s.trim();
+        let mut chars = raw.chars().peekable();
+        let mut segments = vec![];
+
+        if raw.is_empty() {
+            return Err(anyhow!("Empty json path"));
+        }
+        if chars.peek() == Some(&'$') {
+            chars.next();
+        } else if !matches!(chars.peek(), Some('.' | '[')) {
+            // 省略了 `$.` 的写法，比如 `data.id`
+            segments.push(Segment::Child(parse_name(&mut chars, raw)?));
+        }
+
+        while let Some(c) = chars.next() {
+            match c {
+                '.' if chars.peek() == Some(&'.') => {
+                    chars.next();
+                    let selector = match chars.peek() {
+                        Some('[') => {
+                            chars.next();
+                            parse_bracket(&mut chars, raw)?
+                        }
+                        _ => parse_name(&mut chars, raw)?,
+                    };
+                    segments.push(Segment::Descendant(selector));
+                }
+                '.' => segments.push(Segment::Child(parse_name(&mut chars, raw)?)),
+                '[' => segments.push(Segment::Child(parse_bracket(&mut chars, raw)?)),
+                _ => return Err(anyhow!("Invalid json path: {}, unexpected '{}'", raw, c)),
+            }
+        }
+
+        Ok(Self {
+            raw: raw.to_string(),
+            segments,
+        })
+    }
+}
+
+fn parse_name<I>(chars: &mut std::iter::Peekable<I>, raw: &str) -> Result<Selector>
+where
+    I: Iterator<Item = char>,
+{
+    let mut name = String::new();
+    while let Some(&c) = chars.peek() {
+        if c == '.' || c == '[' {
+            break;
+        }
+        name.push(c);
+        chars.next();
+    }
+    match name.as_str() {
+        "" => Err(anyhow!("Invalid json path: {}, empty field name", raw)),
+        "*" => Ok(Selector::Wildcard),
+        _ => Ok(Selector::Key(name)),
+    }
+}
+
+fn parse_bracket<I>(chars: &mut std::iter::Peekable<I>, raw: &str) -> Result<Selector>
+where
+    I: Iterator<Item = char>,
+{
+    let mut inner = String::new();
+    let mut quote = None;
+    let mut quoted = false;
+    loop {
+        let c = chars
+            .next()
+            .ok_or_else(|| anyhow!("Invalid json path: {}, missing ']'", raw))?;
+        match (c, quote) {
+            ('\'' | '"', None) if inner.is_empty() => {
+                quote = Some(c);
+                quoted = true;
+            }
+            (c, Some(q)) if c == q => quote = None,
+            (']', None) => break,
+            (c, _) => inner.push(c),
+        }
+    }
+    // 加引号的内容总是对象的 key，比如 `['1']`、`['*']`
+    if quoted {
+        return Ok(Selector::Key(inner));
+    }
+    if inner == "*" {
+        return Ok(Selector::Wildcard);
+    }
+    match inner.parse::<usize>() {
+        Ok(idx) => Ok(Selector::Index(idx)),
+        Err(_) if !inner.is_empty() => Ok(Selector::Key(inner)),
+        Err(_) => Err(anyhow!("Invalid json path: {}, empty brackets", raw)),
+    }
+}
+
+impl fmt::Display for JsonPath {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.raw)
+    }
+}
+
+impl JsonPath {
+    /// select all the values matched by the path
+    pub fn select<'a>(&self, val: &'a Value) -> Vec<&'a Value> {
+        let mut output = vec![];
+        select_at(val, &self.segments, &mut output);
+        output
+    }
+
+    /// remove all the values matched by the path, return true if anything removed
+    pub fn remove(&self, val: &mut Value) -> bool {
+        remove_at(val, &self.segments)
+    }
+}
+
+fn children<'a>(val: &'a Value, selector: &Selector) -> Vec<&'a Value> {
+    match (selector, val) {
+        (Selector::Key(k), Value::Object(map)) => map.get(k).into_iter().collect(),
+        (Selector::Index(i), Value::Array(arr)) => arr.get(*i).into_iter().collect(),
+        (Selector::Wildcard, Value::Object(map)) => map.values().collect(),
+        (Selector::Wildcard, Value::Array(arr)) => arr.iter().collect(),
+        _ => vec![],
+    }
+}
+
+fn children_mut<'a>(val: &'a mut Value, selector: &Selector) -> Vec<&'a mut Value> {
+    match (selector, val) {
+        (Selector::Key(k), Value::Object(map)) => map.get_mut(k).into_iter().collect(),
+        (Selector::Index(i), Value::Array(arr)) => arr.get_mut(*i).into_iter().collect(),
+        (Selector::Wildcard, Value::Object(map)) => map.values_mut().collect(),
+        (Selector::Wildcard, Value::Array(arr)) => arr.iter_mut().collect(),
+        _ => vec![],
+    }
+}
+
+fn select_at<'a>(val: &'a Value, segments: &[Segment], output: &mut Vec<&'a Value>) {
+    let (segment, rest) = match segments.split_first() {
+        Some(v) => v,
+        None => return output.push(val),
+    };
+    match segment {
+        Segment::Child(selector) => {
+            for child in children(val, selector) {
+                select_at(child, rest, output);
+            }
+        }
+        Segment::Descendant(selector) => {
+            for child in children(val, selector) {
+                select_at(child, rest, output);
+            }
+            for child in children(val, &Selector::Wildcard) {
+                select_at(child, segments, output);
+            }
+        }
+    }
+}
+
+fn remove_children(val: &mut Value, selector: &Selector) -> bool {
+    match (selector, val) {
+        (Selector::Key(k), Value::Object(map)) => map.remove(k).is_some(),
+        (Selector::Index(i), Value::Array(arr)) if *i < arr.len() => {
+            arr.remove(*i);
+            true
+        }
+        (Selector::Wildcard, Value::Object(map)) if !map.is_empty() => {
+            map.clear();
+            true
+        }
+        (Selector::Wildcard, Value::Array(arr)) if !arr.is_empty() => {
+            arr.clear();
+            true
+        }
+        _ => false,
+    }
+}
+
+fn remove_at(val: &mut Value, segments: &[Segment]) -> bool {
+    let (segment, rest) = match segments.split_first() {
+        Some(v) => v,
+        None => return false,
+    };
+    let mut removed = false;
+    match segment {
+        Segment::Child(selector) if rest.is_empty() => removed |= remove_children(val, selector),
+        Segment::Child(selector) => {
+            for child in children_mut(val, selector) {
+                removed |= remove_at(child, rest);
+            }
+        }
+        Segment::Descendant(selector) => {
+            if rest.is_empty() {
+                removed |= remove_children(val, selector);
+            } else {
+                for child in children_mut(val, selector) {
+                    removed |= remove_at(child, rest);
+                }
+            }
+            for child in children_mut(val, &Selector::Wildcard) {
+                removed |= remove_at(child, segments);
+            }
+        }
+    }
+    removed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn json_path_parse_should_work() {
+        let path: JsonPath = "$.data.items[*]..updated_at".parse().unwrap();
+        assert_eq!(
+            path.segments,
+            vec![
+                Segment::Child(Selector::Key("data".into())),
+                Segment::Child(Selector::Key("items".into())),
+                Segment::Child(Selector::Wildcard),
+                Segment::Descendant(Selector::Key("updated_at".into())),
+            ]
+        );
+        let path: JsonPath = "meta['trace.id'][0]".parse().unwrap();
+        assert_eq!(
+            path.segments,
+            vec![
+                Segment::Child(Selector::Key("meta".into())),
+                Segment::Child(Selector::Key("trace.id".into())),
+                Segment::Child(Selector::Index(0)),
+            ]
+        );
+        let path: JsonPath = "codes['1']".parse().unwrap();
+        assert_eq!(
+            path.segments,
+            vec![
+                Segment::Child(Selector::Key("codes".into())),
+                Segment::Child(Selector::Key("1".into())),
+            ]
+        );
+        assert!("$.data[".parse::<JsonPath>().is_err());
+        assert!("$.data..".parse::<JsonPath>().is_err());
+    }
+
+    #[test]
+    fn json_path_select_and_remove_should_work() {
+        let mut val = json!({
+            "id": 1,
+            "meta": {"trace_id": "abc"},
+            "data": {"items": [{"id": 1, "updated_at": 1}, {"id": 2, "updated_at": 2}]}
+        });
+        let path: JsonPath = "data.items[*].updated_at".parse().unwrap();
+        assert_eq!(path.select(&val), vec![&json!(1), &json!(2)]);
+
+        let path: JsonPath = "$..id".parse().unwrap();
+        assert_eq!(path.select(&val).len(), 3);
+        assert!(path.remove(&mut val));
+        assert!(!path.remove(&mut val));
+        assert_eq!(
+            val,
+            json!({
+                "meta": {"trace_id": "abc"},
+                "data": {"items": [{"updated_at": 1}, {"updated_at": 2}]}
+            })
+        );
+    }
+}
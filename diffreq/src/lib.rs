@@ -1,11 +1,26 @@
 mod config;
 pub use config::{
-    get_body_text, get_header_text, get_status_text,
-    xdiff::{DiffConfig, DiffProfile, ResponseProfile},
+    assert::{AssertConfig, BodyAssert, JsonType, ValueMatch},
+    auth::{ApiKeyLocation, AuthConfig},
+    client::{ClientConfig, HttpVersion},
+    encoder::{register_encoder, BodyEncoder, EncodedBody},
+    export::ExportFormat,
+    get_body_syntax, get_body_text, get_header_text, get_status_text,
+    har::{Har, HarEntry},
+    mirror::MirrorConfig,
+    mock::{MockConfig, Stub},
+    oauth2::{ClientAuth, GrantType, OAuth2Config},
+    openapi::{OpenApi, OpenApiAssert},
+    proto::ProtoConfig,
+    xdiff::{DiffChanges, DiffConfig, DiffMode, DiffProfile, DiffResult, ResponseProfile},
     xreq::RequestConfig,
-    Action, Args, ConfigLoad, ConfigValidate, GetProfile, RequestProfile, RunArgs,
+    Action, Args, ConfigLoad, ConfigValidate, ExportArgs, FilteredResponse, FlowArgs, GetProfile,
+    ImportArgs, ImportFormat, MirrorArgs, RawResponse, ReqAction, ReqArgs, ReqImportArgs,
+    ReqRunArgs, RequestProfile, RunArgs, ServeArgs, TestArgs,
 };
 pub mod cli;
+pub mod jsonpath;
+pub mod report;
 pub mod util;
 
 #[derive(Debug, Clone, Eq, PartialEq, Default)]
@@ -13,4 +28,14 @@ pub struct ExtraArgs {
     headers: Vec<(String, String)>,
     query: Vec<(String, String)>,
     body: Vec<(String, String)>,
+    // oauth2 获取的 access token，用于没有配置认证的 profile
+    token: Option<String>,
+}
+
+impl ExtraArgs {
+    /// send the bearer token with the profiles without their own authorization
+    pub fn with_token(mut self, token: impl Into<String>) -> Self {
+        self.token = Some(token.into());
+        self
+    }
 }
@@ -0,0 +1,360 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use console::style;
+use serde::{ser::Error, Serialize, Serializer};
+use serde_json::Value;
+use string_builder::Builder;
+
+use crate::{
+    config::xdiff::{DiffChanges, DiffResult},
+    util::xml_escape,
+    DiffProfile, RequestProfile,
+};
+
+// xdiff 的输出格式，text 为带颜色的终端输出，其他格式主要用于 CI
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum OutputFormat {
+    #[default]
+    Text,
+    Json,
+    Patch,
+    Junit,
+}
+
+// 一个 profile 的 diff 结果，json 格式的输出直接序列化这个结构
+#[derive(Debug, Serialize)]
+pub struct DiffReport<'a> {
+    pub profile: &'a str,
+    #[serde(serialize_with = "serialize_redacted")]
+    pub req1: &'a RequestProfile,
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_redacted"
+    )]
+    pub req2: Option<&'a RequestProfile>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snapshot: Option<&'a str>,
+    pub same: bool,
+    // 请求失败或者响应解析失败时的错误信息
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timing: Option<DiffTiming>,
+    #[serde(flatten)]
+    pub changes: Option<DiffChanges>,
+    #[serde(skip)]
+    pub result: Option<DiffResult>,
+}
+
+// 报告会作为 CI 的产物保存，认证信息和敏感的 header 替换为 `***`
+const SENSITIVE_HEADERS: &[&str] = &["authorization", "proxy-authorization", "cookie"];
+const AUTH_SECRETS: &[&str] = &["password", "token", "value", "secret_key", "session_token"];
+
+fn serialize_redacted<T: Serialize, S: Serializer>(
+    profile: &T,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let mut value = serde_json::to_value(profile).map_err(S::Error::custom)?;
+    redact(&mut value);
+    value.serialize(serializer)
+}
+
+fn redact(profile: &mut Value) {
+    let secret = || Value::String("***".into());
+    let mut sensitive: Vec<String> = SENSITIVE_HEADERS.iter().map(|h| h.to_string()).collect();
+    if let Some(auth) = profile.get_mut("auth").and_then(Value::as_object_mut) {
+        // header 中的 api key
+        if let Some(Value::String(name)) = auth.get("name") {
+            sensitive.push(name.to_lowercase());
+        }
+        for (k, v) in auth.iter_mut() {
+            if AUTH_SECRETS.contains(&k.as_str()) {
+                *v = secret();
+            }
+        }
+    }
+    if let Some(headers) = profile.get_mut("headers").and_then(Value::as_object_mut) {
+        for (k, v) in headers.iter_mut() {
+            if sensitive.contains(&k.to_lowercase()) {
+                *v = secret();
+            }
+        }
+    }
+}
+
+// 两个请求的耗时，单位为毫秒，和快照 diff 时没有 req2
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct DiffTiming {
+    pub req1_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub req2_ms: Option<u64>,
+}
+
+impl<'a> DiffReport<'a> {
+    pub fn new(
+        profile: &'a str,
+        diff_profile: &'a DiffProfile,
+        result: Result<DiffResult>,
+    ) -> Self {
+        let mut report = Self {
+            profile,
+            req1: &diff_profile.req1,
+            req2: diff_profile.req2.as_ref(),
+            snapshot: diff_profile.snapshot.as_deref(),
+            same: false,
+            error: None,
+            timing: None,
+            changes: None,
+            result: None,
+        };
+        match result.and_then(|r| Ok((r.changes()?, r))) {
+            Ok((changes, result)) => {
+                report.same = result.is_same();
+                let (ms1, ms2) = (
+                    result.elapsed1.as_millis() as u64,
+                    result.elapsed2.as_millis() as u64,
+                );
+                report.timing = Some(if result.snapshot {
+                    DiffTiming {
+                        req1_ms: ms2,
+                        req2_ms: None,
+                    }
+                } else {
+                    DiffTiming {
+                        req1_ms: ms1,
+                        req2_ms: Some(ms2),
+                    }
+                });
+                report.changes = Some(changes);
+                report.result = Some(result);
+            }
+            Err(e) => report.error = Some(format!("{:#}", e)),
+        }
+        report
+    }
+}
+
+/// render the diff reports into the given output format
+pub fn render(reports: &[DiffReport], format: OutputFormat) -> Result<String> {
+    match format {
+        OutputFormat::Text => render_text(reports),
+        OutputFormat::Json => Ok(serde_json::to_string_pretty(reports)?),
+        OutputFormat::Patch => render_patch(reports),
+        OutputFormat::Junit => render_junit(reports),
+    }
+}
+
+fn render_text(reports: &[DiffReport]) -> Result<String> {
+    let mut output_builder = Builder::default();
+    for report in reports {
+        if reports.len() > 1 {
+            output_builder.append(format!("{}\n", style(report.profile).bold().cyan()));
+        }
+        match (&report.result, &report.error) {
+            (Some(result), _) => {
+                output_builder.append(result.text()?);
+                if let Some(timing) = &report.timing {
+                    output_builder.append(format!(
+                        "{}\n",
+                        style(match timing.req2_ms {
+                            Some(ms2) => format!("req1: {}ms, req2: {}ms", timing.req1_ms, ms2),
+                            None => format!("req1: {}ms, snapshot", timing.req1_ms),
+                        })
+                        .dim()
+                    ));
+                }
+            }
+            (None, Some(e)) => output_builder.append(format!("{}\n", style(e).red())),
+            _ => {}
+        }
+    }
+    if reports.len() > 1 {
+        output_builder.append(render_summary(reports));
+    }
+    Ok(output_builder.string()?)
+}
+
+// 多个 profile 时输出一个汇总表格
+fn render_summary(reports: &[DiffReport]) -> String {
+    let width = reports
+        .iter()
+        .map(|r| r.profile.len())
+        .chain(std::iter::once("PROFILE".len()))
+        .max()
+        .unwrap_or_default();
+    let mut output = format!("\n{:<width$}  RESULT\n", "PROFILE", width = width);
+    for report in reports {
+        let result = match (&report.error, report.same) {
+            (Some(_), _) => style("error").red(),
+            (None, true) => style("same").green(),
+            (None, false) => style("different").yellow(),
+        };
+        output.push_str(&format!(
+            "{:<width$}  {}\n",
+            report.profile,
+            result,
+            width = width
+        ));
+    }
+    output
+}
+
+fn render_patch(reports: &[DiffReport]) -> Result<String> {
+    let mut output_builder = Builder::default();
+    for report in reports {
+        if let Some(result) = &report.result {
+            output_builder.append(result.patch(report.profile)?);
+        }
+    }
+    Ok(output_builder.string()?)
+}
+
+fn render_junit(reports: &[DiffReport]) -> Result<String> {
+    let mut output_builder = Builder::default();
+    let errors = reports.iter().filter(|r| r.error.is_some()).count();
+    let failures = reports.iter().filter(|r| !r.same).count() - errors;
+    output_builder.append("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    output_builder.append(format!(
+        "<testsuite name=\"xdiff\" tests=\"{}\" failures=\"{}\" errors=\"{}\">\n",
+        reports.len(),
+        failures,
+        errors
+    ));
+    for report in reports {
+        let name = xml_escape(report.profile);
+        let time = report
+            .result
+            .as_ref()
+            .map_or(0.0, |r| r.elapsed1.max(r.elapsed2).as_secs_f64());
+        let body = match (&report.result, &report.error) {
+            (_, Some(e)) => format!("<error message=\"{}\"/>", xml_escape(e)),
+            (Some(result), None) if !report.same => format!(
+                "<failure message=\"responses differ\">{}</failure>",
+                xml_escape(&result.patch(report.profile)?)
+            ),
+            _ => {
+                output_builder.append(format!(
+                    "  <testcase classname=\"xdiff\" name=\"{}\" time=\"{:.3}\"/>\n",
+                    name, time
+                ));
+                continue;
+            }
+        };
+        output_builder.append(format!(
+            "  <testcase classname=\"xdiff\" name=\"{}\" time=\"{:.3}\">\n    {}\n  </testcase>\n",
+            name, time, body
+        ));
+    }
+    output_builder.append("</testsuite>\n");
+    Ok(output_builder.string()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{config::FilteredResponse, DiffMode};
+    use serde_json::json;
+
+    #[test]
+    fn render_junit_should_work() {
+        let res1 = FilteredResponse {
+            status: "HTTP/1.1 200 OK".into(),
+            headers: Default::default(),
+            body: json!({"a": "<1>"}),
+        };
+        let mut res2 = res1.clone();
+        res2.body = json!({"a": "<2>"});
+        let profile = DiffProfile::new(
+            "http://localhost/a".parse().unwrap(),
+            "http://localhost/b".parse().unwrap(),
+            Default::default(),
+        );
+        let result = DiffResult {
+            mode: DiffMode::Text,
+            res1,
+            res2,
+            elapsed1: Default::default(),
+            elapsed2: Default::default(),
+            snapshot: false,
+        };
+        let reports = vec![
+            DiffReport::new("todo", &profile, Ok(result)),
+            DiffReport::new("fail", &profile, Err(anyhow::anyhow!("timeout"))),
+        ];
+        let output = render(&reports, OutputFormat::Junit).unwrap();
+        assert!(output.contains("tests=\"2\" failures=\"1\" errors=\"1\""));
+        assert!(output.contains("<error message=\"timeout\"/>"));
+        assert!(output.contains("-  &quot;a&quot;: &quot;&lt;1&gt;&quot;"));
+        assert!(output.contains("+  &quot;a&quot;: &quot;&lt;2&gt;&quot;"));
+
+        let output = render(&reports, OutputFormat::Json).unwrap();
+        let val: serde_json::Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(val[0]["body"][0]["path"], "$.a");
+        assert_eq!(val[1]["error"], "timeout");
+    }
+
+    #[test]
+    fn json_mode_should_ignore_array_order() {
+        let res1 = FilteredResponse {
+            status: "HTTP/1.1 200 OK".into(),
+            headers: Default::default(),
+            body: json!({"tags": ["a", "b"]}),
+        };
+        let mut res2 = res1.clone();
+        res2.body = json!({"tags": ["b", "a"]});
+        let profile = DiffProfile::new(
+            "http://localhost/a".parse().unwrap(),
+            "http://localhost/b".parse().unwrap(),
+            Default::default(),
+        );
+        let mut result = DiffResult {
+            mode: DiffMode::Json,
+            res1,
+            res2,
+            elapsed1: Default::default(),
+            elapsed2: Default::default(),
+            snapshot: false,
+        };
+        assert!(result.is_same());
+        assert!(DiffReport::new("todo", &profile, Ok(result.clone())).same);
+        result.mode = DiffMode::Text;
+        assert!(!result.is_same());
+    }
+
+    #[test]
+    fn json_report_should_redact_credentials() {
+        let req1: RequestProfile = serde_yaml::from_str(
+            r#"
+url: http://localhost/a
+headers:
+  authorization: Bearer secret
+  cookie: SESSION=secret
+  x-api-key: secret
+  accept: application/json
+auth:
+  type: api_key
+  name: X-Api-Key
+  value: secret
+"#,
+        )
+        .unwrap();
+        let profile = DiffProfile {
+            req1,
+            req2: Some("http://localhost/b".parse().unwrap()),
+            snapshot: None,
+            res: Default::default(),
+            sequential: false,
+            tags: vec![],
+        };
+        let reports = vec![DiffReport::new("todo", &profile, Err(anyhow::anyhow!("x")))];
+        let output = render(&reports, OutputFormat::Json).unwrap();
+        assert!(!output.contains("secret"));
+        let val: serde_json::Value = serde_json::from_str(&output).unwrap();
+        let req1 = &val[0]["req1"];
+        assert_eq!(req1["headers"]["authorization"], "***");
+        assert_eq!(req1["headers"]["x-api-key"], "***");
+        assert_eq!(req1["headers"]["accept"], "application/json");
+        assert_eq!(req1["auth"]["name"], "X-Api-Key");
+        assert_eq!(req1["auth"]["value"], "***");
+    }
+}
@@ -1,7 +1,13 @@
 use anyhow::Result;
 use console::{style, Style};
+use serde::Serialize;
+use serde_json::Value;
 use similar::{ChangeTag, TextDiff};
-use std::fmt;
+use std::{
+    collections::HashSet,
+    fmt,
+    path::{Path, PathBuf},
+};
 use string_builder::Builder;
 use syntect::easy::HighlightLines;
 use syntect::highlighting::ThemeSet;
@@ -57,6 +63,125 @@ pub fn text_diff(text1: &str, text2: &str) -> Result<String> {
     Ok(output_builder.string()?)
 }
 
+// 不带颜色的 unified diff 输出，可以直接作为 patch 使用
+pub fn unified_diff(text1: &str, text2: &str, name1: &str, name2: &str) -> String {
+    TextDiff::from_lines(text1, text2)
+        .unified_diff()
+        .context_radius(3)
+        .header(name1, name2)
+        .to_string()
+}
+
+/// utc (year, month, day, hour, minute, second) of the unix timestamp
+pub fn utc_datetime(secs: i64) -> (i64, i64, i64, i64, i64, i64) {
+    let (days, rem) = (secs.div_euclid(86400), secs.rem_euclid(86400));
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day, rem / 3600, rem % 3600 / 60, rem % 60)
+}
+
+/// profile name from the method and path, non alphanumeric chars are replaced by `_`,
+/// e.g. `get_api_todos_1`
+pub fn profile_name(method: &str, path: &str) -> String {
+    let mut name = method.to_lowercase();
+    for c in path.chars() {
+        let c = if c.is_ascii_alphanumeric() { c } else { '_' };
+        if c != '_' || !name.ends_with('_') {
+            name.push(c.to_ascii_lowercase());
+        }
+    }
+    name.trim_end_matches('_').to_string()
+}
+
+/// append `_2`, `_3`... to the name if it is already used
+pub fn unique_name(names: &mut HashSet<String>, name: &str) -> String {
+    let mut unique = name.to_string();
+    let mut i = 1;
+    while !names.insert(unique.clone()) {
+        i += 1;
+        unique = format!("{}_{}", name, i);
+    }
+    unique
+}
+
+/// the path relative to the base dir, both should be absolute,
+/// e.g. `/a/b/c.yml` relative to `/a/d` is `../b/c.yml`
+pub fn relative_path(path: &Path, base: &Path) -> PathBuf {
+    let components: Vec<_> = path.components().collect();
+    let base: Vec<_> = base.components().collect();
+    let common = components
+        .iter()
+        .zip(&base)
+        .take_while(|(a, b)| a == b)
+        .count();
+    // 没有共同的前缀（比如 windows 上不同的盘符）时只能使用原来的路径
+    if common == 0 {
+        return path.to_path_buf();
+    }
+    let mut relative: PathBuf = base[common..].iter().map(|_| "..").collect();
+    relative.extend(&components[common..]);
+    relative
+}
+
+/// simple glob match, `*` matches any chars and `?` matches one char
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    // 上一个 `*` 在 pattern 和 name 中的位置，用于回溯
+    let (mut p, mut n) = (0, 0);
+    let mut star = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((sp, sn)) => {
+                    star = Some((sp, sn + 1));
+                    p = sp + 1;
+                    n = sn + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// percent-encode all chars except `A-Za-z0-9-_.~`, e.g. a value in the url path
+pub fn encode_uri_component(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            b => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// escape the special characters of xml text and attribute values
+pub fn xml_escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
 pub fn hightlight_text(text: &str, extension: &str, theme_str: &str) -> Result<String> {
     let mut output = Builder::default();
 
@@ -78,3 +203,180 @@ pub fn hightlight_text(text: &str, extension: &str, theme_str: &str) -> Result<S
     }
     Ok(output.string()?)
 }
+
+// json 结构化 diff 的结果，path 使用 json path 的写法，比如：`$.data[3].price`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum JsonChange {
+    Added {
+        path: String,
+        value: Value,
+    },
+    Removed {
+        path: String,
+        value: Value,
+    },
+    Changed {
+        path: String,
+        old: Value,
+        new: Value,
+    },
+}
+
+impl fmt::Display for JsonChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JsonChange::Added { path, value } => write!(f, "+ {}: {}", path, value),
+            JsonChange::Removed { path, value } => write!(f, "- {}: {}", path, value),
+            JsonChange::Changed { path, old, new } => write!(f, "~ {}: {} -> {}", path, old, new),
+        }
+    }
+}
+
+/// compare two json value, object keys and array elements order are ignored
+pub fn json_diff(root: &str, val1: &Value, val2: &Value) -> Vec<JsonChange> {
+    let mut changes = vec![];
+    diff_value(root.to_string(), val1, val2, &mut changes);
+    changes
+}
+
+fn diff_value(path: String, val1: &Value, val2: &Value, changes: &mut Vec<JsonChange>) {
+    match (val1, val2) {
+        (Value::Object(map1), Value::Object(map2)) => {
+            for (k, v1) in map1 {
+                let child = json_path_key(&path, k);
+                match map2.get(k) {
+                    Some(v2) => diff_value(child, v1, v2, changes),
+                    None => changes.push(JsonChange::Removed {
+                        path: child,
+                        value: v1.clone(),
+                    }),
+                }
+            }
+            for (k, v2) in map2.iter().filter(|(k, _)| !map1.contains_key(*k)) {
+                changes.push(JsonChange::Added {
+                    path: json_path_key(&path, k),
+                    value: v2.clone(),
+                });
+            }
+        }
+        (Value::Array(arr1), Value::Array(arr2)) => {
+            // 先把两边相等的元素配对，剩下没有配对上的元素按顺序进行比较
+            let mut matched = vec![false; arr2.len()];
+            let mut left = vec![];
+            for (i, v1) in arr1.iter().enumerate() {
+                match (0..arr2.len()).find(|&j| !matched[j] && &arr2[j] == v1) {
+                    Some(j) => matched[j] = true,
+                    None => left.push(i),
+                }
+            }
+            let right: Vec<usize> = (0..arr2.len()).filter(|&j| !matched[j]).collect();
+            for (&i, &j) in left.iter().zip(right.iter()) {
+                diff_value(format!("{}[{}]", path, i), &arr1[i], &arr2[j], changes);
+            }
+            for &i in left.iter().skip(right.len()) {
+                changes.push(JsonChange::Removed {
+                    path: format!("{}[{}]", path, i),
+                    value: arr1[i].clone(),
+                });
+            }
+            for &j in right.iter().skip(left.len()) {
+                changes.push(JsonChange::Added {
+                    path: format!("{}[{}]", path, j),
+                    value: arr2[j].clone(),
+                });
+            }
+        }
+        _ if val1 != val2 => changes.push(JsonChange::Changed {
+            path,
+            old: val1.clone(),
+            new: val2.clone(),
+        }),
+        _ => {}
+    }
+}
+
+fn json_path_key(path: &str, key: &str) -> String {
+    let plain = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '_' || c == '-');
+    if plain {
+        format!("{}.{}", path, key)
+    } else {
+        format!("{}[{}]", path, Value::String(key.to_string()))
+    }
+}
+
+pub fn json_changes_text(changes: &[JsonChange]) -> String {
+    let mut output_builder = Builder::default();
+    for change in changes {
+        let s = match change {
+            JsonChange::Added { .. } => Style::new().green(),
+            JsonChange::Removed { .. } => Style::new().red(),
+            JsonChange::Changed { .. } => Style::new().yellow(),
+        };
+        output_builder.append(format!("{}\n", s.apply_to(change)));
+    }
+    output_builder.string().unwrap_or_default()
+}
+
+/// write a test fixture into a new temp dir, the dir is removed when the guard is dropped
+#[cfg(test)]
+pub(crate) fn temp_file(
+    name: &str,
+    content: impl AsRef<[u8]>,
+) -> (tempfile::TempDir, std::path::PathBuf) {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join(name);
+    std::fs::write(&path, content).unwrap();
+    (dir, path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn json_diff_should_ignore_order() {
+        let val1 = json!({"a": 1, "b": [1, 2, {"price": 10}], "c": "x"});
+        let val2 = json!({"c": "x", "b": [2, {"price": 12}, 1], "a": 1, "d": true});
+        assert_eq!(
+            json_diff("$", &val1, &val2),
+            vec![
+                JsonChange::Changed {
+                    path: "$.b[2].price".into(),
+                    old: json!(10),
+                    new: json!(12)
+                },
+                JsonChange::Added {
+                    path: "$.d".into(),
+                    value: json!(true)
+                },
+            ]
+        );
+        assert!(json_diff("$", &val1, &val1).is_empty());
+    }
+
+    #[test]
+    fn glob_match_should_work() {
+        assert!(glob_match("todo*", "todo"));
+        assert!(glob_match("todo*", "todo1"));
+        assert!(glob_match("*o?o*", "todo1"));
+        assert!(glob_match("rust", "rust"));
+        assert!(!glob_match("rust", "rusty"));
+        assert!(!glob_match("todo?", "todo"));
+    }
+
+    #[test]
+    fn relative_path_should_work() {
+        let path = Path::new("/a/b/c.yml");
+        assert_eq!(
+            relative_path(path, Path::new("/a/d")),
+            Path::new("../b/c.yml")
+        );
+        assert_eq!(relative_path(path, Path::new("/a/b")), Path::new("c.yml"));
+        assert_eq!(relative_path(path, Path::new("/")), Path::new("a/b/c.yml"));
+    }
+}